@@ -1,36 +1,151 @@
-use std::ops::Div;
+use std::ops::RangeInclusive;
 
 const INPUT: &str = include_str!("../input.txt");
 
 /// Parse the input into a vector of ranges.
 /// Input example: 12-123,333-444
+#[cfg(test)]
 fn parse_input(input: &str) -> Vec<(usize, usize)> {
+    parse_input_in_radix(input, 10)
+}
+
+/// Parse ranges whose bounds are written in the given radix.
+/// Input example for radix 16: 1a-ff,3E8-7d0
+fn parse_input_in_radix(input: &str, radix: u32) -> Vec<(usize, usize)> {
+    assert!((2..=36).contains(&radix), "Unsupported radix: {}", radix);
     input
         .split(",")
         .map(|r| -> (usize, usize) {
-            let mut parts = r.split("-");
-            let start = parts.next().unwrap().parse().unwrap();
-            let end = parts.next().unwrap().parse().unwrap();
+            let mut parts = r.trim().split("-");
+            let start = usize::from_str_radix(parts.next().unwrap(), radix).unwrap();
+            let end = usize::from_str_radix(parts.next().unwrap(), radix).unwrap();
             (start, end)
         })
         .collect()
 }
 
+/// How IDs covered by more than one range are counted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Overlap {
+    /// Every ID counts once, no matter how many ranges contain it.
+    Set,
+    /// Every ID counts once per range containing it.
+    Multiset,
+}
+
+/// Ranges validated by [`normalise_ranges`].
+#[derive(Debug, PartialEq, Eq)]
+struct NormalisedRanges {
+    /// The ranges to evaluate.
+    ranges: Vec<(usize, usize)>,
+    /// Reversed ranges (`end < start`) that were dropped.
+    reversed: Vec<(usize, usize)>,
+}
+
+/// Validate the ranges before evaluating them.
+/// With [`Overlap::Set`] overlapping and adjacent ranges are merged and sorted, so that no ID is
+/// contained in more than one range.
+fn normalise_ranges(input: &[(usize, usize)], overlap: Overlap) -> NormalisedRanges {
+    let (mut ranges, reversed): (Vec<_>, Vec<_>) =
+        input.iter().partition(|&&(start, end)| start <= end);
+
+    if overlap == Overlap::Set && !ranges.is_empty() {
+        ranges.sort();
+        let mut merged_ranges = Vec::new();
+        let (mut current_start, mut current_end) = ranges[0];
+        for &(start, end) in &ranges[1..] {
+            if start <= current_end.saturating_add(1) {
+                current_end = current_end.max(end);
+            } else {
+                merged_ranges.push((current_start, current_end));
+                (current_start, current_end) = (start, end);
+            }
+        }
+        merged_ranges.push((current_start, current_end));
+        ranges = merged_ranges;
+    }
+
+    NormalisedRanges { ranges, reversed }
+}
+
 fn main() {
-    let parsed_input = parse_input(INPUT);
+    // Options: `--radix <2..=36>`, `--input <path>`, `--overlap <set|multiset>` and
+    // `--report <path.csv>`, followed by further invalid-ID policies, e.g. "palindrome & !exactly:2"
+    let mut args = std::env::args().skip(1).peekable();
+    let mut radix = None;
+    let mut input = INPUT.to_string();
+    let mut overlap = Overlap::Multiset;
+    let mut report_path = None;
+    while let Some(option) = args.next_if(|arg| arg.starts_with("--")) {
+        let value = args.next().expect("Missing option value");
+        match option.as_str() {
+            "--radix" => radix = Some(value.parse().expect("Invalid radix")),
+            "--input" => input = std::fs::read_to_string(value).expect("Could not read input"),
+            "--overlap" => {
+                overlap = match value.as_str() {
+                    "set" => Overlap::Set,
+                    "multiset" => Overlap::Multiset,
+                    _ => panic!("Unknown overlap semantics: {}", value),
+                }
+            }
+            "--report" => report_path = Some(value),
+            _ => panic!("Unknown option: {}", option),
+        }
+    }
+    let radix = radix.unwrap_or(10);
 
-    let part_1_result = part_1(&parsed_input);
-    println!("Part 1: {}", part_1_result);
+    // Reports list the ranges as given, the overlap semantics only apply to the totals
+    let input_ranges = parse_input_in_radix(&input, radix);
+    let NormalisedRanges { ranges, reversed } = normalise_ranges(&input_ranges, overlap);
+    for (start, end) in reversed {
+        eprintln!("Warning: ignoring reversed range {}-{}", start, end);
+    }
 
-    let part_2_result = part_2(&parsed_input);
-    println!("Part 2: {}", part_2_result);
+    let mut reports = Vec::new();
+
+    // The puzzle's own policies, in whatever radix the IDs are written
+    println!(
+        "Part 1: {}",
+        sum_invalid_ids(&ranges, &Repetitions::Exactly(2), radix)
+    );
+    println!(
+        "Part 2: {}",
+        sum_invalid_ids(&ranges, &Repetitions::AtLeast(2), radix)
+    );
+
+    if report_path.is_some() {
+        reports.push((
+            "Part 1".to_string(),
+            report(&input_ranges, &Repetitions::Exactly(2), radix),
+        ));
+        reports.push((
+            "Part 2".to_string(),
+            report(&input_ranges, &Repetitions::AtLeast(2), radix),
+        ));
+    }
+
+    for expression in args {
+        let rule = parse_rule(&expression).unwrap_or_else(|error| panic!("{}", error));
+        println!("{}: {}", expression, sum_invalid_ids(&ranges, &rule, radix));
+
+        if report_path.is_some() {
+            reports.push((expression, report(&input_ranges, &rule, radix)));
+        }
+    }
+
+    if let Some(report_path) = report_path {
+        std::fs::write(report_path, report_to_csv(&reports, radix))
+            .expect("Could not write report");
+    }
 }
 
-fn part_1(input: &Vec<(usize, usize)>) -> usize {
-    get_invalid_ids(input, is_valid_id_part_1).iter().sum()
+#[cfg(test)]
+fn part_1(input: &[(usize, usize)]) -> usize {
+    sum_invalid_ids(input, &Repetitions::Exactly(2), 10)
 }
 
 /// An ID is invalid if it is made only of some sequence of digits repeated twice.
+#[cfg(test)]
 fn is_valid_id_part_1(id: usize) -> bool {
     let number_of_digits = id.ilog10() + 1;
     if !number_of_digits.is_multiple_of(2) {
@@ -45,11 +160,13 @@ fn is_valid_id_part_1(id: usize) -> bool {
     first_part != second_part
 }
 
-fn part_2(input: &Vec<(usize, usize)>) -> usize {
-    get_invalid_ids(input, is_valid_id_part_2).iter().sum()
+#[cfg(test)]
+fn part_2(input: &[(usize, usize)]) -> usize {
+    sum_invalid_ids(input, &Repetitions::AtLeast(2), 10)
 }
 
 /// An ID is invalid if it is made only of some sequence of digits repeated at least twice.
+#[cfg(test)]
 fn is_valid_id_part_2(id: usize) -> bool {
     let number_of_digits = id.ilog10() + 1;
 
@@ -58,7 +175,7 @@ fn is_valid_id_part_2(id: usize) -> bool {
             continue;
         }
 
-        let segment_length = number_of_digits.div(segment_count);
+        let segment_length = number_of_digits / segment_count;
         let mut segments = Vec::with_capacity(segment_count as usize);
         let mut remainder = id;
         for _ in 0..segment_count {
@@ -74,11 +191,17 @@ fn is_valid_id_part_2(id: usize) -> bool {
     true
 }
 
-fn get_invalid_ids(input: &Vec<(usize, usize)>, validator: fn(usize) -> bool) -> Vec<usize> {
+/// Checks every single ID in the ranges against the rule, looking at the IDs' digits in `radix`.
+/// Only feasible for small ranges, see [`sum_invalid_ids`] for rules that know a shortcut.
+fn get_invalid_ids<R: IdRule + ?Sized>(
+    input: &[(usize, usize)],
+    rule: &R,
+    radix: u32,
+) -> Vec<usize> {
     let mut invalid_ids = Vec::new();
     for &(start, end) in input {
         for id in start..=end {
-            if !validator(id) {
+            if rule.is_invalid(id, radix) {
                 invalid_ids.push(id);
             }
         }
@@ -86,6 +209,494 @@ fn get_invalid_ids(input: &Vec<(usize, usize)>, validator: fn(usize) -> bool) ->
     invalid_ids
 }
 
+/// Sum of all IDs in the ranges flagged by the rule.
+/// Uses the rule's closed form where it has one, and falls back to checking every ID otherwise.
+fn sum_invalid_ids<R: IdRule + ?Sized>(input: &[(usize, usize)], rule: &R, radix: u32) -> usize {
+    input
+        .iter()
+        .map(|&(start, end)| {
+            rule.sum_invalid_ids(start, end, radix).unwrap_or_else(|| {
+                get_invalid_ids(&[(start, end)], rule, radix)
+                    .iter()
+                    .map(|&id| id as u128)
+                    .sum()
+            })
+        })
+        .sum::<u128>()
+        .try_into()
+        .expect("sum of invalid IDs overflows usize")
+}
+
+/// An invalid ID together with the seed and repetition count that made it invalid, if any.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct InvalidId {
+    id: usize,
+    pattern: Option<(usize, u32)>,
+}
+
+/// The invalid IDs found in one input range.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct RangeReport {
+    start: usize,
+    end: usize,
+    /// The range ends before it starts, so it has no IDs at all.
+    reversed: bool,
+    /// In ascending order.
+    invalid_ids: Vec<InvalidId>,
+}
+
+impl RangeReport {
+    fn count(&self) -> usize {
+        self.invalid_ids.len()
+    }
+
+    fn sum(&self) -> u128 {
+        self.invalid_ids
+            .iter()
+            .map(|invalid_id| invalid_id.id as u128)
+            .sum()
+    }
+
+    fn smallest(&self) -> Option<usize> {
+        self.invalid_ids.first().map(|invalid_id| invalid_id.id)
+    }
+
+    fn largest(&self) -> Option<usize> {
+        self.invalid_ids.last().map(|invalid_id| invalid_id.id)
+    }
+}
+
+/// List the invalid IDs of every range separately, so they can be checked one by one. The ranges
+/// are reported as given, in input order, including reversed ones.
+fn report<R: IdRule + ?Sized>(input: &[(usize, usize)], rule: &R, radix: u32) -> Vec<RangeReport> {
+    input
+        .iter()
+        .map(|&(start, end)| {
+            if start > end {
+                return RangeReport {
+                    start,
+                    end,
+                    reversed: true,
+                    invalid_ids: Vec::new(),
+                };
+            }
+            RangeReport {
+                start,
+                end,
+                reversed: false,
+                invalid_ids: rule
+                    .invalid_ids(start, end, radix)
+                    .unwrap_or_else(|| get_invalid_ids(&[(start, end)], rule, radix))
+                    .into_iter()
+                    .map(|id| InvalidId {
+                        id,
+                        pattern: rule.repetition_pattern(id, radix),
+                    })
+                    .collect(),
+            }
+        })
+        .collect()
+}
+
+/// The shortest seed among `segment_lengths` that `id` repeats, and how often.
+/// For example `1212121212` is `12` repeated 5 times.
+fn repetition_pattern(
+    id: usize,
+    radix: u32,
+    segment_lengths: impl IntoIterator<Item = u32>,
+) -> Option<(usize, u32)> {
+    let number_of_digits = number_of_digits(id, radix);
+    segment_lengths
+        .into_iter()
+        .filter(|&segment_length| number_of_digits.is_multiple_of(segment_length))
+        .find(|&segment_length| {
+            (id as u128).is_multiple_of(repetition_multiplier(
+                number_of_digits,
+                segment_length,
+                radix,
+            ))
+        })
+        .map(|segment_length| {
+            let seed = id % (radix as usize).pow(segment_length);
+            (seed, number_of_digits / segment_length)
+        })
+}
+
+/// `id` written in `radix`, using lowercase letters for digits above 9.
+fn format_in_radix(id: usize, radix: u32) -> String {
+    digits(id, radix)
+        .iter()
+        .rev()
+        .map(|&digit| char::from_digit(digit, radix).unwrap())
+        .collect()
+}
+
+/// Export reports for several rules as CSV, with one row per invalid ID and one row for every
+/// range without any. Range bounds and IDs are written in `radix`, the sums in decimal.
+fn report_to_csv(reports: &[(String, Vec<RangeReport>)], radix: u32) -> String {
+    let mut csv =
+        String::from("rule,range_start,range_end,reversed,count,sum,smallest,largest,id,pattern\n");
+    for (rule, range_reports) in reports {
+        for range_report in range_reports {
+            let range_columns = format!(
+                "\"{}\",{},{},{},{},{},{},{}",
+                rule.replace('"', "\"\""),
+                format_in_radix(range_report.start, radix),
+                format_in_radix(range_report.end, radix),
+                range_report.reversed,
+                range_report.count(),
+                range_report.sum(),
+                range_report
+                    .smallest()
+                    .map(|id| format_in_radix(id, radix))
+                    .unwrap_or_default(),
+                range_report
+                    .largest()
+                    .map(|id| format_in_radix(id, radix))
+                    .unwrap_or_default(),
+            );
+
+            if range_report.invalid_ids.is_empty() {
+                csv.push_str(&format!("{},,\n", range_columns));
+            }
+            for &InvalidId { id, pattern } in &range_report.invalid_ids {
+                let pattern = pattern
+                    .map(|(seed, count)| format!("'{}' × {}", format_in_radix(seed, radix), count))
+                    .unwrap_or_default();
+                csv.push_str(&format!(
+                    "{},{},{}\n",
+                    range_columns,
+                    format_in_radix(id, radix),
+                    pattern
+                ));
+            }
+        }
+    }
+    csv
+}
+
+/// A policy deciding which IDs are invalid, based on their digits in a radix from 2 to 36.
+trait IdRule {
+    fn is_invalid(&self, id: usize, radix: u32) -> bool;
+
+    /// Sum of all invalid IDs in `start..=end`, if the rule can compute it without checking every ID.
+    fn sum_invalid_ids(&self, _start: usize, _end: usize, _radix: u32) -> Option<u128> {
+        None
+    }
+
+    /// All invalid IDs in `start..=end` in ascending order, if the rule can list them without
+    /// checking every ID.
+    fn invalid_ids(&self, _start: usize, _end: usize, _radix: u32) -> Option<Vec<usize>> {
+        None
+    }
+
+    /// The seed and repetition count explaining why `id` is invalid, if it is a repetition.
+    /// Defaults to the shortest seed `id` repeats.
+    fn repetition_pattern(&self, id: usize, radix: u32) -> Option<(usize, u32)> {
+        repetition_pattern(id, radix, 1..number_of_digits(id, radix))
+    }
+
+    /// Invalid if both rules flag the ID.
+    fn and<R: IdRule>(self, other: R) -> And<Self, R>
+    where
+        Self: Sized,
+    {
+        And(self, other)
+    }
+
+    /// Invalid if either rule flags the ID.
+    fn or<R: IdRule>(self, other: R) -> Or<Self, R>
+    where
+        Self: Sized,
+    {
+        Or(self, other)
+    }
+
+    /// Invalid if the rule does not flag the ID.
+    fn not(self) -> Not<Self>
+    where
+        Self: Sized,
+    {
+        Not(self)
+    }
+}
+
+impl IdRule for Box<dyn IdRule> {
+    fn is_invalid(&self, id: usize, radix: u32) -> bool {
+        self.as_ref().is_invalid(id, radix)
+    }
+
+    fn sum_invalid_ids(&self, start: usize, end: usize, radix: u32) -> Option<u128> {
+        self.as_ref().sum_invalid_ids(start, end, radix)
+    }
+
+    fn invalid_ids(&self, start: usize, end: usize, radix: u32) -> Option<Vec<usize>> {
+        self.as_ref().invalid_ids(start, end, radix)
+    }
+
+    fn repetition_pattern(&self, id: usize, radix: u32) -> Option<(usize, u32)> {
+        self.as_ref().repetition_pattern(id, radix)
+    }
+}
+
+/// The ID is made only of some sequence of digits repeated a number of times.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Repetitions {
+    Exactly(u32),
+    AtLeast(u32),
+}
+
+impl Repetitions {
+    /// Seed lengths that make an ID with `number_of_digits` digits invalid under this rule.
+    fn segment_lengths(&self, number_of_digits: u32) -> Vec<u32> {
+        (1..=number_of_digits)
+            .filter(|&segment_length| number_of_digits.is_multiple_of(segment_length))
+            .filter(|&segment_length| match *self {
+                Repetitions::Exactly(count) => number_of_digits / segment_length == count,
+                Repetitions::AtLeast(count) => number_of_digits / segment_length >= count,
+            })
+            .collect()
+    }
+
+    /// Fewer than two repetitions flag every ID, which the closed forms are not meant for.
+    fn is_trivial(&self) -> bool {
+        match *self {
+            Repetitions::Exactly(count) | Repetitions::AtLeast(count) => count < 2,
+        }
+    }
+}
+
+impl IdRule for Repetitions {
+    fn is_invalid(&self, id: usize, radix: u32) -> bool {
+        let number_of_digits = number_of_digits(id, radix);
+        self.segment_lengths(number_of_digits)
+            .into_iter()
+            .any(|segment_length| {
+                (id as u128).is_multiple_of(repetition_multiplier(
+                    number_of_digits,
+                    segment_length,
+                    radix,
+                ))
+            })
+    }
+
+    /// An ID repeating a seed of length `s` also repeats every longer seed whose length is a
+    /// multiple of `s` and divides the number of digits, so plainly adding up all seed lengths
+    /// would count it several times. Inclusion-exclusion over the divisibility order of the seed
+    /// lengths counts each ID exactly once.
+    fn sum_invalid_ids(&self, start: usize, end: usize, radix: u32) -> Option<u128> {
+        if self.is_trivial() {
+            return None;
+        }
+
+        let mut sum: i128 = 0;
+        for number_of_digits in 1..=max_digits(radix) {
+            let segment_lengths = self.segment_lengths(number_of_digits);
+
+            // Longest seeds first, so every coefficient only depends on already known ones
+            let mut coefficients: Vec<i128> = vec![0; segment_lengths.len()];
+            for i in (0..segment_lengths.len()).rev() {
+                coefficients[i] = 1
+                    - (i + 1..segment_lengths.len())
+                        .filter(|&j| segment_lengths[j].is_multiple_of(segment_lengths[i]))
+                        .map(|j| coefficients[j])
+                        .sum::<i128>();
+            }
+
+            for (&segment_length, coefficient) in segment_lengths.iter().zip(coefficients) {
+                if coefficient != 0 {
+                    sum += coefficient
+                        * sum_repeated_ids(start, end, number_of_digits, segment_length, radix)
+                            as i128;
+                }
+            }
+        }
+        Some(sum as u128)
+    }
+
+    fn invalid_ids(&self, start: usize, end: usize, radix: u32) -> Option<Vec<usize>> {
+        if self.is_trivial() {
+            return None;
+        }
+
+        let mut invalid_ids = Vec::new();
+        for number_of_digits in 1..=max_digits(radix) {
+            for segment_length in self.segment_lengths(number_of_digits) {
+                let multiplier = repetition_multiplier(number_of_digits, segment_length, radix);
+                invalid_ids.extend(
+                    repeated_seeds(start, end, number_of_digits, segment_length, radix)
+                        .map(|seed| (seed * multiplier) as usize),
+                );
+            }
+        }
+        // IDs repeating several seed lengths were generated once for each of them
+        invalid_ids.sort_unstable();
+        invalid_ids.dedup();
+        Some(invalid_ids)
+    }
+
+    fn repetition_pattern(&self, id: usize, radix: u32) -> Option<(usize, u32)> {
+        let segment_lengths = self.segment_lengths(number_of_digits(id, radix));
+        repetition_pattern(id, radix, segment_lengths)
+    }
+}
+
+/// The ID reads the same forwards and backwards.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Palindrome;
+
+impl IdRule for Palindrome {
+    fn is_invalid(&self, id: usize, radix: u32) -> bool {
+        let digits = digits(id, radix);
+        digits.iter().eq(digits.iter().rev())
+    }
+}
+
+/// The sum of the ID's digits lies in the given range.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct DigitSum(RangeInclusive<u32>);
+
+impl IdRule for DigitSum {
+    fn is_invalid(&self, id: usize, radix: u32) -> bool {
+        self.0.contains(&digits(id, radix).iter().sum())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct And<A, B>(A, B);
+
+impl<A: IdRule, B: IdRule> IdRule for And<A, B> {
+    fn is_invalid(&self, id: usize, radix: u32) -> bool {
+        self.0.is_invalid(id, radix) && self.1.is_invalid(id, radix)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Or<A, B>(A, B);
+
+impl<A: IdRule, B: IdRule> IdRule for Or<A, B> {
+    fn is_invalid(&self, id: usize, radix: u32) -> bool {
+        self.0.is_invalid(id, radix) || self.1.is_invalid(id, radix)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Not<A>(A);
+
+impl<A: IdRule> IdRule for Not<A> {
+    fn is_invalid(&self, id: usize, radix: u32) -> bool {
+        !self.0.is_invalid(id, radix)
+    }
+}
+
+/// Parse a rule expression such as `at-least:3 | palindrome & !digit-sum:10-20`.
+///
+/// Available rules are `exactly:K`, `at-least:K`, `palindrome` and `digit-sum:A-B`.
+/// `!` binds tightest, then `&`, then `|`.
+fn parse_rule(expression: &str) -> Result<Box<dyn IdRule>, String> {
+    if let Some((left, right)) = expression.split_once('|') {
+        return Ok(Box::new(parse_rule(left)?.or(parse_rule(right)?)));
+    }
+    if let Some((left, right)) = expression.split_once('&') {
+        return Ok(Box::new(parse_rule(left)?.and(parse_rule(right)?)));
+    }
+
+    let expression = expression.trim();
+    if let Some(inner) = expression.strip_prefix('!') {
+        return Ok(Box::new(parse_rule(inner)?.not()));
+    }
+
+    let (name, argument) = expression.split_once(':').unwrap_or((expression, ""));
+    let parse_count = |argument: &str| -> Result<u32, String> {
+        match argument.parse() {
+            Ok(count) if count >= 2 => Ok(count),
+            _ => Err(format!("Invalid repetition count: {}", argument)),
+        }
+    };
+    match name {
+        "exactly" => Ok(Box::new(Repetitions::Exactly(parse_count(argument)?))),
+        "at-least" => Ok(Box::new(Repetitions::AtLeast(parse_count(argument)?))),
+        "palindrome" => Ok(Box::new(Palindrome)),
+        "digit-sum" => {
+            let (min, max) = argument
+                .split_once('-')
+                .and_then(|(min, max)| Some((min.parse().ok()?, max.parse().ok()?)))
+                .ok_or_else(|| format!("Invalid digit sum range: {}", argument))?;
+            Ok(Box::new(DigitSum(min..=max)))
+        }
+        _ => Err(format!("Unknown rule: {}", expression)),
+    }
+}
+
+/// Number of digits of `id` in `radix`, counting `0` as a single digit.
+fn number_of_digits(id: usize, radix: u32) -> u32 {
+    id.checked_ilog(radix as usize).unwrap_or(0) + 1
+}
+
+/// Digits of `id` in `radix`, least significant first.
+fn digits(mut id: usize, radix: u32) -> Vec<u32> {
+    let mut digits = vec![(id % radix as usize) as u32];
+    id /= radix as usize;
+    while id > 0 {
+        digits.push((id % radix as usize) as u32);
+        id /= radix as usize;
+    }
+    digits
+}
+
+/// Largest number of digits in `radix` a `usize` ID can have.
+fn max_digits(radix: u32) -> u32 {
+    number_of_digits(usize::MAX, radix)
+}
+
+/// `1 + r^s + r^2s + ...` for radix `r`, which turns an `s`-digit seed into the
+/// `number_of_digits`-digit ID repeating it.
+fn repetition_multiplier(number_of_digits: u32, segment_length: u32, radix: u32) -> u128 {
+    let radix = radix as u128;
+    (radix.pow(number_of_digits) - 1) / (radix.pow(segment_length) - 1)
+}
+
+/// Sum of all IDs in `start..=end` with exactly `number_of_digits` digits in `radix` that consist
+/// of a `segment_length`-digit seed repeated `number_of_digits / segment_length` times.
+///
+/// Every such ID is `seed * multiplier`, so the seeds inside the range form a contiguous range
+/// themselves and their sum is an arithmetic series.
+fn sum_repeated_ids(
+    start: usize,
+    end: usize,
+    number_of_digits: u32,
+    segment_length: u32,
+    radix: u32,
+) -> u128 {
+    let multiplier = repetition_multiplier(number_of_digits, segment_length, radix);
+    let seeds = repeated_seeds(start, end, number_of_digits, segment_length, radix);
+    if seeds.is_empty() {
+        return 0;
+    }
+
+    let seed_count = seeds.end() - seeds.start() + 1;
+    multiplier * (seeds.start() + seeds.end()) * seed_count / 2
+}
+
+/// The seeds whose repetition is an ID in `start..=end`, see [`sum_repeated_ids`].
+fn repeated_seeds(
+    start: usize,
+    end: usize,
+    number_of_digits: u32,
+    segment_length: u32,
+    radix: u32,
+) -> RangeInclusive<u128> {
+    debug_assert!(number_of_digits.is_multiple_of(segment_length));
+
+    let multiplier = repetition_multiplier(number_of_digits, segment_length, radix);
+    let smallest_seed = (radix as u128).pow(segment_length - 1);
+    let largest_seed = (radix as u128).pow(segment_length) - 1;
+
+    let first_seed = (start as u128).div_ceil(multiplier).max(smallest_seed);
+    let last_seed = (end as u128 / multiplier).min(largest_seed);
+    first_seed..=last_seed
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -106,74 +717,74 @@ mod tests {
 
     #[test]
     fn test_is_valid_id_part_1() {
-        assert_eq!(is_valid_id_part_1(55), false);
-        assert_eq!(is_valid_id_part_1(6464), false);
-        assert_eq!(is_valid_id_part_1(123123), false);
-        assert_eq!(is_valid_id_part_1(101), true);
-        assert_eq!(is_valid_id_part_1(12341234), false);
-        assert_eq!(is_valid_id_part_1(123123123), true);
-        assert_eq!(is_valid_id_part_1(1212121212), true);
-        assert_eq!(is_valid_id_part_1(1111111), true);
-        assert_eq!(is_valid_id_part_1(3), true);
+        assert!(!is_valid_id_part_1(55));
+        assert!(!is_valid_id_part_1(6464));
+        assert!(!is_valid_id_part_1(123123));
+        assert!(is_valid_id_part_1(101));
+        assert!(!is_valid_id_part_1(12341234));
+        assert!(is_valid_id_part_1(123123123));
+        assert!(is_valid_id_part_1(1212121212));
+        assert!(is_valid_id_part_1(1111111));
+        assert!(is_valid_id_part_1(3));
     }
 
     #[test]
     fn test_is_valid_id_part_2() {
-        assert_eq!(is_valid_id_part_2(55), false);
-        assert_eq!(is_valid_id_part_2(6464), false);
-        assert_eq!(is_valid_id_part_2(123123), false);
-        assert_eq!(is_valid_id_part_2(101), true);
-        assert_eq!(is_valid_id_part_2(12341234), false);
-        assert_eq!(is_valid_id_part_2(123123123), false);
-        assert_eq!(is_valid_id_part_2(1212121212), false);
-        assert_eq!(is_valid_id_part_2(1111111), false);
-        assert_eq!(is_valid_id_part_2(3), true);
+        assert!(!is_valid_id_part_2(55));
+        assert!(!is_valid_id_part_2(6464));
+        assert!(!is_valid_id_part_2(123123));
+        assert!(is_valid_id_part_2(101));
+        assert!(!is_valid_id_part_2(12341234));
+        assert!(!is_valid_id_part_2(123123123));
+        assert!(!is_valid_id_part_2(1212121212));
+        assert!(!is_valid_id_part_2(1111111));
+        assert!(is_valid_id_part_2(3));
     }
 
     #[test]
     fn test_get_invalid_ids_part_1() {
         assert_eq!(
-            get_invalid_ids(&vec![(11, 22)], is_valid_id_part_1),
+            get_invalid_ids(&[(11, 22)], &Repetitions::Exactly(2), 10),
             vec![11, 22]
         );
         assert_eq!(
-            get_invalid_ids(&vec![(95, 115)], is_valid_id_part_1),
+            get_invalid_ids(&[(95, 115)], &Repetitions::Exactly(2), 10),
             vec![99]
         );
         assert_eq!(
-            get_invalid_ids(&vec![(998, 1012)], is_valid_id_part_1),
+            get_invalid_ids(&[(998, 1012)], &Repetitions::Exactly(2), 10),
             vec![1010]
         );
         assert_eq!(
-            get_invalid_ids(&vec![(1188511880, 1188511890)], is_valid_id_part_1),
+            get_invalid_ids(&[(1188511880, 1188511890)], &Repetitions::Exactly(2), 10),
             vec![1188511885]
         );
         assert_eq!(
-            get_invalid_ids(&vec![(222220, 222224)], is_valid_id_part_1),
+            get_invalid_ids(&[(222220, 222224)], &Repetitions::Exactly(2), 10),
             vec![222222]
         );
         assert_eq!(
-            get_invalid_ids(&vec![(1698522, 1698528)], is_valid_id_part_1),
+            get_invalid_ids(&[(1698522, 1698528)], &Repetitions::Exactly(2), 10),
             vec![]
         );
         assert_eq!(
-            get_invalid_ids(&vec![(446443, 446449)], is_valid_id_part_1),
+            get_invalid_ids(&[(446443, 446449)], &Repetitions::Exactly(2), 10),
             vec![446446]
         );
         assert_eq!(
-            get_invalid_ids(&vec![(38593856, 38593862)], is_valid_id_part_1),
+            get_invalid_ids(&[(38593856, 38593862)], &Repetitions::Exactly(2), 10),
             vec![38593859]
         );
         assert_eq!(
-            get_invalid_ids(&vec![(565653, 565659)], is_valid_id_part_1),
+            get_invalid_ids(&[(565653, 565659)], &Repetitions::Exactly(2), 10),
             vec![]
         );
         assert_eq!(
-            get_invalid_ids(&vec![(824824821, 824824827)], is_valid_id_part_1),
+            get_invalid_ids(&[(824824821, 824824827)], &Repetitions::Exactly(2), 10),
             vec![]
         );
         assert_eq!(
-            get_invalid_ids(&vec![(2121212118, 2121212124)], is_valid_id_part_1),
+            get_invalid_ids(&[(2121212118, 2121212124)], &Repetitions::Exactly(2), 10),
             vec![]
         );
     }
@@ -181,51 +792,406 @@ mod tests {
     #[test]
     fn test_get_invalid_ids_part_2() {
         assert_eq!(
-            get_invalid_ids(&vec![(11, 22)], is_valid_id_part_2),
+            get_invalid_ids(&[(11, 22)], &Repetitions::AtLeast(2), 10),
             vec![11, 22]
         );
         assert_eq!(
-            get_invalid_ids(&vec![(95, 115)], is_valid_id_part_2),
+            get_invalid_ids(&[(95, 115)], &Repetitions::AtLeast(2), 10),
             vec![99, 111]
         );
         assert_eq!(
-            get_invalid_ids(&vec![(998, 1012)], is_valid_id_part_2),
+            get_invalid_ids(&[(998, 1012)], &Repetitions::AtLeast(2), 10),
             vec![999, 1010]
         );
         assert_eq!(
-            get_invalid_ids(&vec![(1188511880, 1188511890)], is_valid_id_part_2),
+            get_invalid_ids(&[(1188511880, 1188511890)], &Repetitions::AtLeast(2), 10),
             vec![1188511885]
         );
         assert_eq!(
-            get_invalid_ids(&vec![(222220, 222224)], is_valid_id_part_2),
+            get_invalid_ids(&[(222220, 222224)], &Repetitions::AtLeast(2), 10),
             vec![222222]
         );
         assert_eq!(
-            get_invalid_ids(&vec![(1698522, 1698528)], is_valid_id_part_2),
+            get_invalid_ids(&[(1698522, 1698528)], &Repetitions::AtLeast(2), 10),
             vec![]
         );
         assert_eq!(
-            get_invalid_ids(&vec![(446443, 446449)], is_valid_id_part_2),
+            get_invalid_ids(&[(446443, 446449)], &Repetitions::AtLeast(2), 10),
             vec![446446]
         );
         assert_eq!(
-            get_invalid_ids(&vec![(38593856, 38593862)], is_valid_id_part_2),
+            get_invalid_ids(&[(38593856, 38593862)], &Repetitions::AtLeast(2), 10),
             vec![38593859]
         );
         assert_eq!(
-            get_invalid_ids(&vec![(565653, 565659)], is_valid_id_part_2),
+            get_invalid_ids(&[(565653, 565659)], &Repetitions::AtLeast(2), 10),
             vec![565656]
         );
         assert_eq!(
-            get_invalid_ids(&vec![(824824821, 824824827)], is_valid_id_part_2),
+            get_invalid_ids(&[(824824821, 824824827)], &Repetitions::AtLeast(2), 10),
             vec![824824824]
         );
         assert_eq!(
-            get_invalid_ids(&vec![(2121212118, 2121212124)], is_valid_id_part_2),
+            get_invalid_ids(&[(2121212118, 2121212124)], &Repetitions::AtLeast(2), 10),
             vec![2121212121]
         );
     }
 
+    #[test]
+    fn test_sum_repeated_ids_matches_scan() {
+        let ranges = vec![
+            (1, 10_000),
+            (95, 115),
+            (998, 1012),
+            (123_000, 456_789),
+            (1_000_000, 1_100_000),
+            (2121212118, 2121212124),
+        ];
+        for range in ranges {
+            let input = vec![range];
+            assert_eq!(
+                part_1(&input),
+                get_invalid_ids(&input, &Repetitions::Exactly(2), 10)
+                    .iter()
+                    .sum::<usize>()
+            );
+            assert_eq!(
+                part_2(&input),
+                get_invalid_ids(&input, &Repetitions::AtLeast(2), 10)
+                    .iter()
+                    .sum::<usize>()
+            );
+        }
+    }
+
+    #[test]
+    fn test_sum_repeated_ids_huge_range() {
+        // 11 + 22 + ... + 99, plus 111 + 222 + ... + 999 for part 2
+        assert_eq!(part_1(&[(1, 99)]), 495);
+        assert_eq!(part_2(&[(1, 999)]), 495 + 111 * 45);
+
+        // Far too large to scan, but splitting the range must not change the sum
+        let whole = [(1, 1_000_000_000_000)];
+        let split = [(1, 123_456_789_123), (123_456_789_124, 1_000_000_000_000)];
+        assert_eq!(part_1(&whole), part_1(&split));
+        assert_eq!(part_2(&whole), part_2(&split));
+    }
+
+    #[test]
+    fn test_repetitions_match_validators() {
+        for id in 1..200_000 {
+            assert_eq!(
+                Repetitions::Exactly(2).is_invalid(id, 10),
+                !is_valid_id_part_1(id)
+            );
+            assert_eq!(
+                Repetitions::AtLeast(2).is_invalid(id, 10),
+                !is_valid_id_part_2(id)
+            );
+        }
+    }
+
+    #[test]
+    fn test_repetitions_sum_matches_scan() {
+        let rules = [
+            Repetitions::Exactly(3),
+            Repetitions::Exactly(4),
+            Repetitions::AtLeast(3),
+            Repetitions::AtLeast(4),
+        ];
+        for rule in rules {
+            for &(start, end) in &[(1, 1_200_000), (123_000, 1_230_000)] {
+                assert_eq!(
+                    rule.sum_invalid_ids(start, end, 10),
+                    Some(
+                        get_invalid_ids(&[(start, end)], &rule, 10)
+                            .iter()
+                            .sum::<usize>() as u128
+                    )
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_rules() {
+        assert!(Palindrome.is_invalid(12321, 10));
+        assert!(Palindrome.is_invalid(7, 10));
+        assert!(!Palindrome.is_invalid(1231, 10));
+        assert!(DigitSum(10..=12).is_invalid(3331, 10));
+        assert!(!DigitSum(10..=12).is_invalid(3335, 10));
+        assert!(Repetitions::Exactly(3).is_invalid(121212, 10));
+        assert!(!Repetitions::Exactly(3).is_invalid(1212, 10));
+        assert!(Repetitions::AtLeast(3).is_invalid(111111, 10));
+        assert!(!Repetitions::AtLeast(3).is_invalid(123123, 10));
+    }
+
+    #[test]
+    fn test_combinators() {
+        let rule = Palindrome.and(Repetitions::Exactly(2).not());
+        assert!(rule.is_invalid(121, 10));
+        assert!(rule.is_invalid(1221, 10));
+        assert!(!rule.is_invalid(1111, 10));
+        assert!(!rule.is_invalid(1212, 10));
+
+        let rule = Palindrome.or(DigitSum(1..=1));
+        assert!(rule.is_invalid(100, 10));
+        assert!(rule.is_invalid(99, 10));
+        assert!(!rule.is_invalid(12, 10));
+    }
+
+    #[test]
+    fn test_parse_rule() {
+        let rule = parse_rule("palindrome & !exactly:2").unwrap();
+        assert_eq!(
+            get_invalid_ids(&[(100, 1300)], &rule, 10),
+            get_invalid_ids(
+                &[(100, 1300)],
+                &Palindrome.and(Repetitions::Exactly(2).not()),
+                10
+            )
+        );
+
+        let rule = parse_rule("at-least:3 | digit-sum:1-1").unwrap();
+        assert_eq!(
+            get_invalid_ids(&[(95, 1000)], &rule, 10),
+            vec![100, 111, 222, 333, 444, 555, 666, 777, 888, 999, 1000]
+        );
+
+        assert!(parse_rule("exactly:1").is_err());
+        assert!(parse_rule("digit-sum:3").is_err());
+        assert!(parse_rule("prime").is_err());
+    }
+
+    #[test]
+    fn test_sum_invalid_ids_falls_back_to_scan() {
+        assert_eq!(sum_invalid_ids(&[(10, 30)], &Palindrome, 10), 11 + 22);
+        assert_eq!(
+            sum_invalid_ids(&[(10, 30)], &parse_rule("exactly:2").unwrap(), 10),
+            11 + 22
+        );
+    }
+
+    #[test]
+    fn test_normalise_ranges() {
+        let input = vec![(10, 20), (30, 25), (15, 22), (23, 24), (40, 50), (1, 2)];
+        assert_eq!(
+            normalise_ranges(&input, Overlap::Set),
+            NormalisedRanges {
+                ranges: vec![(1, 2), (10, 24), (40, 50)],
+                reversed: vec![(30, 25)]
+            }
+        );
+        assert_eq!(
+            normalise_ranges(&input, Overlap::Multiset),
+            NormalisedRanges {
+                ranges: vec![(10, 20), (15, 22), (23, 24), (40, 50), (1, 2)],
+                reversed: vec![(30, 25)]
+            }
+        );
+        assert_eq!(
+            normalise_ranges(&[(5, 1)], Overlap::Set),
+            NormalisedRanges {
+                ranges: vec![],
+                reversed: vec![(5, 1)]
+            }
+        );
+        assert_eq!(
+            normalise_ranges(&[(1, usize::MAX), (usize::MAX, usize::MAX)], Overlap::Set).ranges,
+            vec![(1, usize::MAX)]
+        );
+    }
+
+    #[test]
+    fn test_overlap_semantics() {
+        let input = vec![(11, 22), (11, 22), (20, 40), (99, 95)];
+        let multiset = normalise_ranges(&input, Overlap::Multiset).ranges;
+        let set = normalise_ranges(&input, Overlap::Set).ranges;
+        assert_eq!(part_1(&multiset), 2 * (11 + 22) + 22 + 33);
+        assert_eq!(part_1(&set), 11 + 22 + 33);
+    }
+
+    #[test]
+    fn test_repetitions_invalid_ids_match_scan() {
+        for radix in [2, 10, 16] {
+            for rule in [
+                Repetitions::Exactly(2),
+                Repetitions::AtLeast(2),
+                Repetitions::Exactly(3),
+            ] {
+                assert_eq!(
+                    rule.invalid_ids(95, 54_321, radix),
+                    Some(get_invalid_ids(&[(95, 54_321)], &rule, radix))
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_report() {
+        let reports = report(
+            &[(95, 115), (1698522, 1698528), (998, 1012)],
+            &Repetitions::AtLeast(2),
+            10,
+        );
+        assert_eq!(
+            reports[0].invalid_ids,
+            vec![
+                InvalidId {
+                    id: 99,
+                    pattern: Some((9, 2))
+                },
+                InvalidId {
+                    id: 111,
+                    pattern: Some((1, 3))
+                }
+            ]
+        );
+        assert_eq!(reports[0].count(), 2);
+        assert_eq!(reports[0].sum(), 210);
+        assert_eq!(reports[0].smallest(), Some(99));
+        assert_eq!(reports[0].largest(), Some(111));
+        assert_eq!(reports[1].count(), 0);
+        assert_eq!(reports[1].smallest(), None);
+        assert_eq!(reports[2].smallest(), Some(999));
+        assert_eq!(reports[2].largest(), Some(1010));
+
+        // Ranges are reported as given, overlapping or reversed
+        let reports = report(
+            &[(10, 30), (20, 40), (50, 40)],
+            &Repetitions::Exactly(2),
+            10,
+        );
+        assert_eq!(reports.len(), 3);
+        assert_eq!((reports[1].start, reports[1].sum()), (20, 22 + 33));
+        assert!(reports[2].reversed && reports[2].invalid_ids.is_empty());
+
+        // The pattern explains the rule, not just the shortest repetition
+        let reports = report(&[(1111, 1111)], &Repetitions::Exactly(2), 10);
+        assert_eq!(reports[0].invalid_ids[0].pattern, Some((11, 2)));
+
+        // Falls back to scanning for rules without a generator
+        let reports = report(&[(95, 115)], &Palindrome, 10);
+        assert_eq!(
+            reports[0]
+                .invalid_ids
+                .iter()
+                .map(|invalid_id| (invalid_id.id, invalid_id.pattern))
+                .collect::<Vec<_>>(),
+            vec![(99, Some((9, 2))), (101, None), (111, Some((1, 3)))]
+        );
+    }
+
+    #[test]
+    fn test_repetition_pattern() {
+        assert_eq!(Palindrome.repetition_pattern(1212121212, 10), Some((12, 5)));
+        assert_eq!(Palindrome.repetition_pattern(111111, 10), Some((1, 6)));
+        assert_eq!(Palindrome.repetition_pattern(123123, 10), Some((123, 2)));
+        assert_eq!(Palindrome.repetition_pattern(101, 10), None);
+        assert_eq!(Palindrome.repetition_pattern(7, 10), None);
+        assert_eq!(Palindrome.repetition_pattern(0xABAB, 16), Some((0xAB, 2)));
+        assert_eq!(
+            Repetitions::Exactly(2).repetition_pattern(111111, 10),
+            Some((111, 2))
+        );
+        assert_eq!(
+            Repetitions::Exactly(3).repetition_pattern(111111, 10),
+            Some((11, 3))
+        );
+        assert_eq!(
+            Repetitions::AtLeast(3).repetition_pattern(111111, 10),
+            Some((1, 6))
+        );
+    }
+
+    #[test]
+    fn test_report_to_csv() {
+        let reports = [
+            (
+                "Part 2".to_string(),
+                report(
+                    &[(95, 115), (1698522, 1698528), (20, 10)],
+                    &Repetitions::AtLeast(2),
+                    10,
+                ),
+            ),
+            (
+                "exactly:2".to_string(),
+                report(&[(0xA0, 0xB0)], &Repetitions::Exactly(2), 16),
+            ),
+        ];
+        assert_eq!(
+            report_to_csv(&reports[..1], 10),
+            "rule,range_start,range_end,reversed,count,sum,smallest,largest,id,pattern
+\"Part 2\",95,115,false,2,210,99,111,99,'9' × 2
+\"Part 2\",95,115,false,2,210,99,111,111,'1' × 3
+\"Part 2\",1698522,1698528,false,0,0,,,,
+\"Part 2\",20,10,true,0,0,,,,
+"
+        );
+        assert!(
+            report_to_csv(&reports[1..], 16)
+                .ends_with("\"exactly:2\",a0,b0,false,1,170,aa,aa,aa,'a' × 2\n")
+        );
+    }
+
+    #[test]
+    fn test_parse_input_in_radix() {
+        assert_eq!(
+            parse_input_in_radix("1a-ff,3E8-7d0", 16),
+            vec![(26, 255), (1000, 2000)]
+        );
+        assert_eq!(parse_input_in_radix("101-1111", 2), vec![(5, 15)]);
+        assert_eq!(parse_input_in_radix("12-123", 10), parse_input("12-123"));
+    }
+
+    #[test]
+    fn test_rules_in_radix() {
+        // 0b1010 is "10" twice, 0b101 is not a repetition
+        assert!(Repetitions::Exactly(2).is_invalid(0b1010, 2));
+        assert!(!Repetitions::Exactly(2).is_invalid(0b101, 2));
+        assert!(Repetitions::AtLeast(2).is_invalid(0xABABAB, 16));
+        assert!(!Repetitions::Exactly(2).is_invalid(0xABABAB, 16));
+        assert!(Repetitions::Exactly(2).is_invalid(0x1010, 16));
+        assert!(!Repetitions::Exactly(2).is_invalid(0x1010, 10));
+        assert!(Palindrome.is_invalid(0b1001, 2));
+        assert!(!Palindrome.is_invalid(0b110, 2));
+        assert!(Palindrome.is_invalid(0b110, 10));
+        assert!(DigitSum(30..=30).is_invalid(0xFF, 16));
+    }
+
+    #[test]
+    fn test_repetitions_sum_in_radix_matches_scan() {
+        for radix in [2, 3, 7, 16, 36] {
+            for rule in [
+                Repetitions::Exactly(2),
+                Repetitions::AtLeast(2),
+                Repetitions::AtLeast(3),
+            ] {
+                for &(start, end) in &[(1, 100_000), (4_321, 54_321)] {
+                    assert_eq!(
+                        rule.sum_invalid_ids(start, end, radix),
+                        Some(
+                            get_invalid_ids(&[(start, end)], &rule, radix)
+                                .iter()
+                                .sum::<usize>() as u128
+                        )
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_sum_invalid_ids_full_binary_range() {
+        // Every usize has at most 64 binary digits, so this must not overflow the multipliers
+        assert!(
+            Repetitions::AtLeast(2)
+                .sum_invalid_ids(1, usize::MAX, 2)
+                .is_some()
+        );
+    }
+
     #[test]
     fn test_part_1_simple() {
         let input = vec![
@@ -1,3 +1,9 @@
+use std::cmp::Ordering;
+use std::collections::HashSet;
+use std::fmt;
+use std::iter::Sum;
+use std::ops::Add;
+
 use include_lines::include_lines;
 
 const INPUT: &[&str] = &include_lines!("./day_03/input.txt");
@@ -11,6 +17,69 @@ fn main() {
         .collect();
     println!("Part 1: {}", part_1(&banks));
     println!("Part 2: {}", part_2(&banks));
+
+    // Options: `--smallest`, `--min-gap <n>`, `--max-span <n>`, `--exclude <i,j,...>` and
+    // `--budget <n>`, optionally followed by a config file listing the selection lengths to sweep
+    // over
+    let mut args = std::env::args().skip(1).peekable();
+    let mut objective = Objective::Largest;
+    let mut constraints = Constraints::default();
+    let mut customised = false;
+    while let Some(option) = args.next_if(|arg| arg.starts_with("--")) {
+        let mut value = || args.next().expect("Missing option value");
+        if option == "--budget" {
+            let budget = value().parse().expect("Invalid budget");
+            let allocation = distribute_budget(&banks, budget);
+            println!("Budget {}: {}", budget, allocation.total);
+            continue;
+        }
+
+        customised = true;
+        match option.as_str() {
+            "--smallest" => objective = Objective::Smallest,
+            "--min-gap" => constraints.min_gap = value().parse().expect("Invalid gap"),
+            "--max-span" => constraints.max_span = Some(value().parse().expect("Invalid span")),
+            "--exclude" => constraints.excluded = parse_number_list(&value()).into_iter().collect(),
+            _ => panic!("Unknown option: {}", option),
+        }
+    }
+
+    let selection_lengths = match args.next() {
+        Some(config_path) => {
+            let config = std::fs::read_to_string(config_path).expect("Could not read config");
+            parse_number_list(&config)
+        }
+        None if customised => vec![2, 12],
+        None => Vec::new(),
+    };
+    for k in selection_lengths {
+        let selections: Vec<Selection> = banks
+            .iter()
+            .filter_map(|bank| select(bank, k, objective, &constraints))
+            .collect();
+        let total: Joltage = selections.iter().map(|selection| &selection.value).sum();
+        println!("k = {}: {}", k, total);
+        if selections.len() < banks.len() {
+            eprintln!(
+                "Warning: {} banks cannot satisfy the constraints for k = {}",
+                banks.len() - selections.len(),
+                k
+            );
+        }
+    }
+}
+
+/// Parse a list of numbers, such as the selection lengths to sweep over or the excluded positions,
+/// separated by whitespace or commas.
+/// Everything after a `#` on a line is a comment.
+fn parse_number_list(config: &str) -> Vec<usize> {
+    config
+        .lines()
+        .map(|line| line.split('#').next().unwrap())
+        .flat_map(|line| line.split(|c: char| c == ',' || c.is_whitespace()))
+        .filter(|k| !k.is_empty())
+        .map(|k| k.parse().unwrap())
+        .collect()
 }
 
 fn bank_input_to_batteries(input: &str) -> Vec<usize> {
@@ -20,49 +89,346 @@ fn bank_input_to_batteries(input: &str) -> Vec<usize> {
         .collect()
 }
 
-fn part_1(banks: &[Vec<usize>]) -> usize {
+fn part_1(banks: &[Vec<usize>]) -> Joltage {
     banks
         .iter()
         .map(|bank| bank_largest_joultage::<2>(bank))
         .sum()
 }
 
-fn part_2(banks: &[Vec<usize>]) -> usize {
+fn part_2(banks: &[Vec<usize>]) -> Joltage {
     banks
         .iter()
         .map(|bank| bank_largest_joultage::<12>(bank))
         .sum()
 }
 
-fn bank_largest_joultage<const N: usize>(bank: &[usize]) -> usize {
-    assert!(bank.len() >= N);
+fn bank_largest_joultage<const N: usize>(bank: &[usize]) -> Joltage {
+    select_largest(bank, N).value
+}
+
+/// A non-negative number with arbitrarily many decimal digits, so that long selections and their
+/// sums do not overflow.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+struct Joltage {
+    /// Least significant digit first, without leading zeros. Zero has no digits at all.
+    digits: Vec<u8>,
+}
+
+impl Joltage {
+    /// The number reading the given digits, most significant first.
+    fn from_digits(digits: impl IntoIterator<Item = usize>) -> Self {
+        let mut digits: Vec<u8> = digits.into_iter().map(|digit| digit as u8).collect();
+        digits.reverse();
+        while digits.last() == Some(&0) {
+            digits.pop();
+        }
+        Joltage { digits }
+    }
+}
+
+impl From<usize> for Joltage {
+    fn from(value: usize) -> Self {
+        Joltage::from_digits(value.to_string().bytes().map(|b| (b - b'0') as usize))
+    }
+}
+
+impl Ord for Joltage {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.digits
+            .len()
+            .cmp(&other.digits.len())
+            .then_with(|| self.digits.iter().rev().cmp(other.digits.iter().rev()))
+    }
+}
+
+impl PartialOrd for Joltage {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Add for &Joltage {
+    type Output = Joltage;
+
+    fn add(self, other: &Joltage) -> Joltage {
+        let mut digits = Vec::with_capacity(self.digits.len().max(other.digits.len()) + 1);
+        let mut carry = 0;
+        for i in 0..self.digits.len().max(other.digits.len()) {
+            let sum = self.digits.get(i).unwrap_or(&0) + other.digits.get(i).unwrap_or(&0) + carry;
+            digits.push(sum % 10);
+            carry = sum / 10;
+        }
+        if carry > 0 {
+            digits.push(carry);
+        }
+        Joltage { digits }
+    }
+}
+
+impl<'a> Sum<&'a Joltage> for Joltage {
+    fn sum<I: Iterator<Item = &'a Joltage>>(iter: I) -> Self {
+        iter.fold(Joltage::default(), |total, value| &total + value)
+    }
+}
 
-    let mut chosen: [usize; N] = bank[0..N].try_into().unwrap();
+impl Sum for Joltage {
+    fn sum<I: Iterator<Item = Joltage>>(iter: I) -> Self {
+        iter.fold(Joltage::default(), |total, value| &total + &value)
+    }
+}
 
-    for battery in bank.iter().skip(N) {
-        // If there is a i<j in the chosen list with chosen[i] < chosen[j], throw out i, and append battery
-        let mut already_inserted = false;
-        for i in 0..N - 1 {
-            if chosen[i] < chosen[i + 1] {
-                for j in i..N - 1 {
-                    chosen[j] = chosen[j + 1];
+impl fmt::Display for Joltage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.digits.is_empty() {
+            return write!(f, "0");
+        }
+        for digit in self.digits.iter().rev() {
+            write!(f, "{}", digit)?;
+        }
+        Ok(())
+    }
+}
+
+/// How a total battery budget is split across the banks.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct BudgetAllocation {
+    /// Sum of the joltages of all banks.
+    total: Joltage,
+    /// Number of batteries chosen from each bank.
+    per_bank: Vec<usize>,
+}
+
+/// Distribute at most `budget` batteries across the banks such that the summed joltage is as
+/// large as possible, every bank contributing the largest number of its chosen length.
+///
+/// The best joltage of a bank only depends on how many batteries it gets, so this is a knapsack
+/// over the banks: after looking at some banks, `best[used]` is the largest total they reach
+/// with at most `used` batteries.
+fn distribute_budget(banks: &[Vec<usize>], budget: usize) -> BudgetAllocation {
+    let mut best: Vec<Joltage> = vec![Joltage::default(); budget + 1];
+    // batteries_given[b][used] = batteries bank b gets in the best total of banks 0..=b
+    let mut batteries_given: Vec<Vec<usize>> = Vec::with_capacity(banks.len());
+
+    for bank in banks {
+        let bank_values: Vec<Joltage> = (0..=bank.len().min(budget))
+            .map(|k| select_largest(bank, k).value)
+            .collect();
+
+        let mut next_best = vec![Joltage::default(); budget + 1];
+        let mut given = vec![0; budget + 1];
+        for used in 0..=budget {
+            for (k, value) in bank_values.iter().enumerate().take(used + 1) {
+                let total = &best[used - k] + value;
+                if total > next_best[used] {
+                    next_best[used] = total;
+                    given[used] = k;
                 }
-                chosen[N - 1] = *battery;
-                already_inserted = true;
+            }
+        }
+        best = next_best;
+        batteries_given.push(given);
+    }
+
+    let mut per_bank = vec![0; banks.len()];
+    let mut used = budget;
+    for (b, given) in batteries_given.iter().enumerate().rev() {
+        per_bank[b] = given[used];
+        used -= given[used];
+    }
+    BudgetAllocation {
+        total: best.swap_remove(budget),
+        per_bank,
+    }
+}
+
+/// Whether the chosen joltage should be as large or as small as possible.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Objective {
+    Largest,
+    Smallest,
+}
+
+impl Objective {
+    /// Whether a choice comparing as `ordering` to another one is strictly better.
+    fn prefers(&self, ordering: Ordering) -> bool {
+        match self {
+            Objective::Largest => ordering == Ordering::Greater,
+            Objective::Smallest => ordering == Ordering::Less,
+        }
+    }
+}
+
+/// Restrictions on which batteries of a bank may be chosen together.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct Constraints {
+    /// Minimum number of batteries left out between two chosen ones.
+    min_gap: usize,
+    /// Maximum distance between the positions of the first and the last chosen battery.
+    max_span: Option<usize>,
+    /// Positions that must not be chosen.
+    excluded: HashSet<usize>,
+}
+
+/// The batteries chosen from a bank, in bank order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Selection {
+    /// The joltage of the chosen batteries read as one number.
+    value: Joltage,
+    /// Positions of the chosen batteries in the bank.
+    indices: Vec<usize>,
+}
+
+impl Selection {
+    fn from_indices(bank: &[usize], indices: Vec<usize>) -> Self {
+        let value = Joltage::from_digits(indices.iter().map(|&i| bank[i]));
+        Selection { value, indices }
+    }
+}
+
+/// Choose `k` batteries, keeping their order, such that their joltage is as large as possible.
+/// Runs in linear time, see [`select_unconstrained`].
+fn select_largest(bank: &[usize], k: usize) -> Selection {
+    assert!(bank.len() >= k);
+    select(bank, k, Objective::Largest, &Constraints::default()).unwrap()
+}
+
+/// Choose `k` batteries, keeping their order, such that their joltage is optimal for the
+/// objective while honouring the constraints. Returns `None` if the constraints leave no way to
+/// choose `k` batteries.
+fn select(
+    bank: &[usize],
+    k: usize,
+    objective: Objective,
+    constraints: &Constraints,
+) -> Option<Selection> {
+    if bank.len() < k {
+        return None;
+    }
+    if *constraints == Constraints::default() {
+        return Some(select_unconstrained(bank, k, objective));
+    }
+
+    let allowed: Vec<bool> = (0..bank.len())
+        .map(|i| !constraints.excluded.contains(&i))
+        .collect();
+    let last_position = bank.len() - 1;
+
+    let Some(max_span) = constraints.max_span else {
+        let chosen = select_in_window(
+            bank,
+            k,
+            objective,
+            &allowed,
+            constraints.min_gap,
+            0,
+            last_position,
+        )?;
+        return Some(Selection::from_indices(bank, chosen));
+    };
+
+    // Nothing to choose, even if no battery is allowed
+    if k == 0 {
+        return Some(Selection::from_indices(bank, Vec::new()));
+    }
+    // The first battery fixes the window all others have to fit into, so the earliest optimal
+    // first battery is not necessarily the best one. Try all of them.
+    let mut best: Option<Vec<usize>> = None;
+    for first in (0..bank.len()).filter(|&first| allowed[first]) {
+        let window_end = (first + max_span).min(last_position);
+        let mut window_allowed = allowed.clone();
+        window_allowed[..first].fill(false);
+        let Some(chosen) = select_in_window(
+            bank,
+            k,
+            objective,
+            &window_allowed,
+            constraints.min_gap,
+            first,
+            window_end,
+        ) else {
+            continue;
+        };
+        if chosen[0] != first {
+            continue;
+        }
+        let is_better = match &best {
+            None => true,
+            Some(best) => objective.prefers(
+                chosen
+                    .iter()
+                    .map(|&i| bank[i])
+                    .cmp(best.iter().map(|&i| bank[i])),
+            ),
+        };
+        if is_better {
+            best = Some(chosen);
+        }
+    }
+    best.map(|chosen| Selection::from_indices(bank, chosen))
+}
+
+/// The monotonic stack: a new battery throws out worse ones before it as long as enough
+/// batteries remain to still fill all `k` slots. Every battery is pushed and popped at most once,
+/// so this runs in linear time. Among equal choices the earliest batteries are kept.
+fn select_unconstrained(bank: &[usize], k: usize, objective: Objective) -> Selection {
+    let mut chosen: Vec<usize> = Vec::with_capacity(k);
+    for (i, &battery) in bank.iter().enumerate() {
+        let remaining = bank.len() - i;
+        while let Some(&last) = chosen.last() {
+            if !objective.prefers(battery.cmp(&bank[last])) || chosen.len() - 1 + remaining < k {
                 break;
             }
+            chosen.pop();
         }
-        // Otherwise, check if we can replace the last element with the new battery
-        if !already_inserted && chosen[N - 1] < *battery {
-            chosen[N - 1] = *battery;
+        if chosen.len() < k {
+            chosen.push(i);
         }
     }
+    Selection::from_indices(bank, chosen)
+}
+
+/// Greedily choose `k` allowed batteries from `window_start..=window_end` with at least `min_gap`
+/// batteries between them: every slot takes the best battery that still leaves enough room for
+/// the remaining slots, the earliest one on ties, as that leaves the most room for later slots.
+fn select_in_window(
+    bank: &[usize],
+    k: usize,
+    objective: Objective,
+    allowed: &[bool],
+    min_gap: usize,
+    window_start: usize,
+    window_end: usize,
+) -> Option<Vec<usize>> {
+    // max_picks[i] = most batteries that can still be chosen from i..=window_end
+    let mut max_picks = vec![0; window_end + min_gap + 3];
+    for i in (window_start..=window_end).rev() {
+        let picking = if allowed[i] {
+            1 + max_picks[i + 1 + min_gap]
+        } else {
+            0
+        };
+        max_picks[i] = max_picks[i + 1].max(picking);
+    }
 
-    let mut result = 0;
-    for battery in chosen.iter() {
-        result = result * 10 + battery;
+    let mut chosen = Vec::with_capacity(k);
+    let mut next = window_start;
+    while chosen.len() < k {
+        let slots_left = k - chosen.len();
+        let mut best: Option<usize> = None;
+        for i in next..=window_end {
+            if !allowed[i] || 1 + max_picks[i + 1 + min_gap] < slots_left {
+                continue;
+            }
+            if best.is_none_or(|best| objective.prefers(bank[i].cmp(&bank[best]))) {
+                best = Some(i);
+            }
+        }
+        let best = best?;
+        chosen.push(best);
+        next = best + 1 + min_gap;
     }
-    result
+    Some(chosen)
 }
 
 #[cfg(test)]
@@ -78,19 +444,19 @@ mod tests {
     fn test_bank_largest_joultage_2() {
         assert_eq!(
             bank_largest_joultage::<2>(&bank_input_to_batteries("987654321111111")),
-            98
+            Joltage::from(98)
         );
         assert_eq!(
             bank_largest_joultage::<2>(&bank_input_to_batteries("811111111111119")),
-            89
+            Joltage::from(89)
         );
         assert_eq!(
             bank_largest_joultage::<2>(&bank_input_to_batteries("234234234234278")),
-            78
+            Joltage::from(78)
         );
         assert_eq!(
             bank_largest_joultage::<2>(&bank_input_to_batteries("818181911112111")),
-            92
+            Joltage::from(92)
         );
     }
 
@@ -98,19 +464,285 @@ mod tests {
     fn test_bank_largest_joultage_12() {
         assert_eq!(
             bank_largest_joultage::<12>(&bank_input_to_batteries("987654321111111")),
-            987654321111
+            Joltage::from(987654321111)
         );
         assert_eq!(
             bank_largest_joultage::<12>(&bank_input_to_batteries("811111111111119")),
-            811111111119
+            Joltage::from(811111111119)
         );
         assert_eq!(
             bank_largest_joultage::<12>(&bank_input_to_batteries("234234234234278")),
-            434234234278
+            Joltage::from(434234234278)
         );
         assert_eq!(
             bank_largest_joultage::<12>(&bank_input_to_batteries("818181911112111")),
-            888911112111
+            Joltage::from(888911112111)
+        );
+        // Beyond what fits a usize
+        assert_eq!(
+            bank_largest_joultage::<24>(&bank_input_to_batteries("9876543219876543219876543"))
+                .to_string(),
+            "987654329876543219876543"
+        );
+    }
+
+    #[test]
+    fn test_select_largest() {
+        let bank = bank_input_to_batteries("818181911112111");
+        assert_eq!(
+            select_largest(&bank, 2),
+            Selection {
+                value: Joltage::from(92),
+                indices: vec![6, 11]
+            }
+        );
+        assert_eq!(select_largest(&bank, 0).value, Joltage::from(0));
+        assert_eq!(
+            select_largest(&bank, 15).value,
+            Joltage::from(818181911112111)
+        );
+        assert_eq!(
+            select_largest(&bank_input_to_batteries("234234234234278"), 3),
+            Selection {
+                value: Joltage::from(478),
+                indices: vec![2, 13, 14]
+            }
+        );
+    }
+
+    #[test]
+    fn test_select_largest_matches_brute_force() {
+        let bank = bank_input_to_batteries("3141592653589793");
+        for k in 0..=6 {
+            // Try every subset of k positions
+            let mut best = 0;
+            for mask in 0u32..(1 << bank.len()) {
+                if mask.count_ones() as usize != k {
+                    continue;
+                }
+                let value = (0..bank.len())
+                    .filter(|i| mask & (1 << i) != 0)
+                    .fold(0, |value, i| value * 10 + bank[i]);
+                best = best.max(value);
+            }
+            assert_eq!(select_largest(&bank, k).value, Joltage::from(best));
+        }
+    }
+
+    /// All ways to choose `k` positions honouring the constraints, by trying every subset.
+    fn brute_force(
+        bank: &[usize],
+        k: usize,
+        objective: Objective,
+        constraints: &Constraints,
+    ) -> Option<Joltage> {
+        let mut values = Vec::new();
+        for mask in 0u32..(1 << bank.len()) {
+            if mask.count_ones() as usize != k {
+                continue;
+            }
+            let indices: Vec<usize> = (0..bank.len()).filter(|i| mask & (1 << i) != 0).collect();
+            let gaps_ok = indices
+                .windows(2)
+                .all(|pair| pair[1] - pair[0] > constraints.min_gap);
+            let span_ok = constraints.max_span.is_none_or(|max_span| {
+                indices.is_empty() || indices[indices.len() - 1] - indices[0] <= max_span
+            });
+            let excluded_ok = indices.iter().all(|i| !constraints.excluded.contains(i));
+            if gaps_ok && span_ok && excluded_ok {
+                values.push(Selection::from_indices(bank, indices).value);
+            }
+        }
+        match objective {
+            Objective::Largest => values.into_iter().max(),
+            Objective::Smallest => values.into_iter().min(),
+        }
+    }
+
+    #[test]
+    fn test_select_constrained_matches_brute_force() {
+        let bank = bank_input_to_batteries("314159265358979");
+        let constraint_sets = [
+            Constraints::default(),
+            Constraints {
+                min_gap: 1,
+                ..Default::default()
+            },
+            Constraints {
+                max_span: Some(6),
+                ..Default::default()
+            },
+            Constraints {
+                excluded: HashSet::from([5, 12, 13]),
+                ..Default::default()
+            },
+            Constraints {
+                min_gap: 2,
+                max_span: Some(9),
+                excluded: HashSet::from([0, 7]),
+            },
+        ];
+        for constraints in &constraint_sets {
+            for objective in [Objective::Largest, Objective::Smallest] {
+                for k in 0..=6 {
+                    assert_eq!(
+                        select(&bank, k, objective, constraints).map(|selection| selection.value),
+                        brute_force(&bank, k, objective, constraints),
+                        "{:?} {:?} k = {}",
+                        constraints,
+                        objective,
+                        k
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_select_constrained() {
+        let bank = bank_input_to_batteries("987654321111111");
+        assert_eq!(
+            select(&bank, 2, Objective::Smallest, &Constraints::default()),
+            Some(Selection {
+                value: Joltage::from(11),
+                indices: vec![8, 9]
+            })
+        );
+        let constraints = Constraints {
+            min_gap: 1,
+            ..Default::default()
+        };
+        assert_eq!(
+            select(&bank, 3, Objective::Largest, &constraints),
+            Some(Selection {
+                value: Joltage::from(975),
+                indices: vec![0, 2, 4]
+            })
+        );
+        let constraints = Constraints {
+            max_span: Some(1),
+            excluded: HashSet::from([0]),
+            ..Default::default()
+        };
+        assert_eq!(
+            select(&bank, 2, Objective::Largest, &constraints)
+                .unwrap()
+                .value,
+            Joltage::from(87)
+        );
+        assert_eq!(select(&bank, 3, Objective::Largest, &constraints), None);
+        let constraints = Constraints {
+            max_span: Some(1),
+            excluded: HashSet::from([0, 1]),
+            ..Default::default()
+        };
+        assert_eq!(
+            select(&[1, 2], 0, Objective::Largest, &constraints),
+            Some(Selection {
+                value: Joltage::from(0),
+                indices: vec![]
+            })
+        );
+        assert_eq!(
+            select(&bank, 16, Objective::Largest, &Constraints::default()),
+            None
+        );
+    }
+
+    #[test]
+    fn test_distribute_budget() {
+        let banks = vec![
+            bank_input_to_batteries("9119"),
+            bank_input_to_batteries("54321"),
+            bank_input_to_batteries("18"),
+        ];
+        assert_eq!(
+            distribute_budget(&banks, 0),
+            BudgetAllocation {
+                total: Joltage::from(0),
+                per_bank: vec![0, 0, 0]
+            }
+        );
+        // A longer number beats any sum of shorter ones: 919 > 99 + 8
+        assert_eq!(
+            distribute_budget(&banks, 3),
+            BudgetAllocation {
+                total: Joltage::from(919),
+                per_bank: vec![3, 0, 0]
+            }
+        );
+
+        // Compare with every possible split of the budget
+        for budget in 0..=11 {
+            let mut best = Joltage::default();
+            for a in 0..=4 {
+                for b in 0..=5 {
+                    for c in 0..=2 {
+                        if a + b + c <= budget {
+                            let total = [(0, a), (1, b), (2, c)]
+                                .iter()
+                                .map(|&(bank, k)| select_largest(&banks[bank], k).value)
+                                .sum();
+                            best = best.max(total);
+                        }
+                    }
+                }
+            }
+            let allocation = distribute_budget(&banks, budget);
+            assert_eq!(allocation.total, best);
+            assert!(allocation.per_bank.iter().sum::<usize>() <= budget);
+            let recomputed: Joltage = banks
+                .iter()
+                .zip(&allocation.per_bank)
+                .map(|(bank, &k)| select_largest(bank, k).value)
+                .sum();
+            assert_eq!(recomputed, allocation.total);
+        }
+    }
+
+    #[test]
+    fn test_joltage() {
+        assert_eq!(Joltage::from(0).to_string(), "0");
+        assert_eq!(Joltage::from_digits([0, 0, 4, 2]).to_string(), "42");
+        assert_eq!(Joltage::from_digits([0, 0]), Joltage::from(0));
+        assert_eq!(
+            (&Joltage::from(usize::MAX) + &Joltage::from(1)).to_string(),
+            "18446744073709551616"
+        );
+        assert!(Joltage::from(99) < Joltage::from(100));
+        assert!(Joltage::from(123) < Joltage::from(132));
+        assert_eq!(
+            [Joltage::from(999), Joltage::from(1), Joltage::from(0)]
+                .iter()
+                .sum::<Joltage>(),
+            Joltage::from(1000)
+        );
+    }
+
+    #[test]
+    fn test_select_largest_long() {
+        let bank = bank_input_to_batteries("1234567898765432123456789876543212345678987654321");
+        assert_eq!(
+            select_largest(&bank, 30).value.to_string(),
+            "987789876543212345678987654321"
+        );
+
+        // Two 25-digit joltages sum beyond what a usize can hold
+        let banks = vec![bank_input_to_batteries(&"9".repeat(30)); 2];
+        let total: Joltage = banks
+            .iter()
+            .map(|bank| select_largest(bank, 25).value)
+            .collect::<Vec<_>>()
+            .iter()
+            .sum();
+        assert_eq!(total.to_string(), format!("1{}8", "9".repeat(24)));
+    }
+
+    #[test]
+    fn test_parse_number_list() {
+        assert_eq!(
+            parse_number_list("2, 12\n# comment\n3 4 # trailing\n"),
+            vec![2, 12, 3, 4]
         );
     }
 
@@ -123,7 +755,7 @@ mod tests {
             bank_input_to_batteries("818181911112111"),
         ];
 
-        assert_eq!(part_1(&banks), 357);
+        assert_eq!(part_1(&banks), Joltage::from(357));
     }
 
     #[test]
@@ -132,7 +764,7 @@ mod tests {
             .iter()
             .map(|line| bank_input_to_batteries(line))
             .collect();
-        assert_eq!(part_1(&banks), 17524);
+        assert_eq!(part_1(&banks), Joltage::from(17524));
     }
 
     #[test]
@@ -144,7 +776,7 @@ mod tests {
             bank_input_to_batteries("818181911112111"),
         ];
 
-        assert_eq!(part_2(&banks), 3121910778619);
+        assert_eq!(part_2(&banks), Joltage::from(3121910778619));
     }
 
     #[test]
@@ -153,6 +785,6 @@ mod tests {
             .iter()
             .map(|line| bank_input_to_batteries(line))
             .collect();
-        assert_eq!(part_2(&banks), 173848577117276);
+        assert_eq!(part_2(&banks), Joltage::from(173848577117276));
     }
 }
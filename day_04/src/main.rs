@@ -1,73 +1,802 @@
-use std::collections::HashSet;
-
 const INPUT: &str = include_str!("../input.txt");
 
 fn main() {
     println!("Part 1: {}", part_1(INPUT));
     println!("Part 2: {}", part_2(INPUT));
+
+    // Options: `--input <path>`, `--neighbourhood <4|8|moore:R|hex|6|26>`, `--threshold <n>`,
+    // `--boundary <open|torus|walls>`, `--timeline <directory>` to export when every roll was
+    // removed and `--core <path>` to export the components of the rolls that are never removed
+    let mut args = std::env::args().skip(1).peekable();
+    if args.peek().is_none() {
+        return;
+    }
+    let mut input = INPUT.to_string();
+    let mut rules = Rules::default();
+    let mut timeline_directory = None;
+    let mut core_path = None;
+    while let Some(option) = args.next() {
+        let value = args.next().expect("Missing option value");
+        match option.as_str() {
+            "--input" => input = std::fs::read_to_string(value).expect("Could not read input"),
+            "--neighbourhood" => {
+                rules.neighbourhood = value.parse().unwrap_or_else(|error| panic!("{}", error))
+            }
+            "--threshold" => rules.threshold = value.parse().expect("Invalid threshold"),
+            "--boundary" => {
+                rules.boundary = value.parse().unwrap_or_else(|error| panic!("{}", error))
+            }
+            "--timeline" => timeline_directory = Some(std::path::PathBuf::from(value)),
+            "--core" => core_path = Some(std::path::PathBuf::from(value)),
+            _ => panic!("Unknown option: {}", option),
+        }
+    }
+
+    let mut grid = parse_input(&input, rules).unwrap_or_else(|error| {
+        eprintln!("Error: {}", error);
+        std::process::exit(1);
+    });
+    let initial_grid = grid.clone();
+    let rounds = removal_rounds(&mut grid);
+    println!(
+        "First round: {}",
+        rounds.first().map_or(0, |round| round.len())
+    );
+    if grid.depth > 1 {
+        let removed = grid.count_per_layer(rounds.iter().flatten().copied());
+        for (z, count) in removed.iter().enumerate() {
+            println!("Layer {}: {}", z + 1, count);
+        }
+    }
+    println!(
+        "Total: {}",
+        rounds.iter().map(|round| round.len()).sum::<usize>()
+    );
+
+    let components = grid.components();
+    let mut sizes = components.sizes.clone();
+    sizes.sort_unstable_by(|a, b| b.cmp(a));
+    println!(
+        "Stable core: {} rolls in {} components",
+        grid.roll_count(),
+        sizes.len()
+    );
+    if !sizes.is_empty() {
+        let sizes: Vec<String> = sizes.iter().map(|size| size.to_string()).collect();
+        println!("Component sizes: {}", sizes.join(", "));
+    }
+    if let Some(path) = core_path {
+        std::fs::write(path, components.labelled_grid(grid.width, grid.height))
+            .expect("Could not export stable core");
+    }
+
+    if let Some(directory) = timeline_directory {
+        let timeline = Timeline::new(&initial_grid, &rounds);
+        export_timeline(&timeline, &directory).expect("Could not export timeline");
+    }
 }
 
-fn parse_input(input: &str) -> HashSet<(usize, usize)> {
-    let mut set = HashSet::new();
-    for (y, line) in input.lines().enumerate() {
-        for (x, c) in line.chars().enumerate() {
-            if c == '@' {
-                set.insert((x, y));
+/// Write the timeline as `timeline.txt`, `frames.txt`, `timeline.ppm` and `timeline.png`, plus
+/// one `frame_NNNN.png` per round for animations.
+fn export_timeline(timeline: &Timeline, directory: &std::path::Path) -> std::io::Result<()> {
+    std::fs::create_dir_all(directory)?;
+    std::fs::write(directory.join("timeline.txt"), timeline.labelled_grid())?;
+    std::fs::write(directory.join("frames.txt"), timeline.frames().join("\n"))?;
+    let image = timeline.image(IMAGE_SCALE);
+    std::fs::write(directory.join("timeline.ppm"), image.to_ppm())?;
+    std::fs::write(directory.join("timeline.png"), image.to_png())?;
+    for round in 0..=timeline.round_count {
+        std::fs::write(
+            directory.join(format!("frame_{:04}.png", round)),
+            timeline.frame_image(round, IMAGE_SCALE).to_png(),
+        )?;
+    }
+    Ok(())
+}
+
+/// Which cells around a roll count as its neighbours.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Neighbourhood {
+    /// The 4 cells sharing an edge.
+    VonNeumann,
+    /// All cells at most `radius` steps away in both directions, i.e. the 8 surrounding cells for
+    /// radius 1.
+    Moore { radius: usize },
+    /// The 6 cells around a hexagon, with odd rows shifted half a cell to the right.
+    Hexagonal,
+    /// The 6 cells sharing a face, in the same layer and the layers above and below.
+    Faces,
+    /// The 26 cells of the surrounding 3x3x3 cube.
+    Cube,
+}
+
+impl Neighbourhood {
+    /// Offsets `(dx, dy, dz)` of the neighbours of a cell in row `y`. Only the 3D neighbourhoods
+    /// reach into other layers.
+    fn offsets(&self, y: usize) -> Vec<(isize, isize, isize)> {
+        match *self {
+            Neighbourhood::VonNeumann => vec![(0, -1, 0), (-1, 0, 0), (1, 0, 0), (0, 1, 0)],
+            Neighbourhood::Moore { radius } => {
+                let radius = radius as isize;
+                (-radius..=radius)
+                    .flat_map(|dy| (-radius..=radius).map(move |dx| (dx, dy, 0)))
+                    .filter(|&offset| offset != (0, 0, 0))
+                    .collect()
+            }
+            Neighbourhood::Hexagonal => {
+                // The rows above and below reach half a cell further towards the shifted side
+                let shift = if y.is_multiple_of(2) { -1 } else { 1 };
+                vec![
+                    (0, -1, 0),
+                    (shift, -1, 0),
+                    (-1, 0, 0),
+                    (1, 0, 0),
+                    (0, 1, 0),
+                    (shift, 1, 0),
+                ]
             }
+            Neighbourhood::Faces => vec![
+                (0, 0, -1),
+                (0, -1, 0),
+                (-1, 0, 0),
+                (1, 0, 0),
+                (0, 1, 0),
+                (0, 0, 1),
+            ],
+            Neighbourhood::Cube => (-1..=1)
+                .flat_map(|dz| (-1..=1).flat_map(move |dy| (-1..=1).map(move |dx| (dx, dy, dz))))
+                .filter(|&offset| offset != (0, 0, 0))
+                .collect(),
         }
     }
-    set
 }
 
-fn part_1(input: &str) -> usize {
-    let mut set = parse_input(input);
+impl std::str::FromStr for Neighbourhood {
+    type Err = String;
 
-    let count_before = set.len();
-    take_removable_rolls(&mut set);
-    let count_after = set.len();
-    count_before - count_after
+    /// `4`, `8`, `moore:R`, `hex`, or `6` and `26` in 3D.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "4" => Ok(Neighbourhood::VonNeumann),
+            "8" => Ok(Neighbourhood::Moore { radius: 1 }),
+            "hex" => Ok(Neighbourhood::Hexagonal),
+            "6" => Ok(Neighbourhood::Faces),
+            "26" => Ok(Neighbourhood::Cube),
+            _ => s
+                .strip_prefix("moore:")
+                .and_then(|radius| radius.parse().ok())
+                .map(|radius| Neighbourhood::Moore { radius })
+                .ok_or_else(|| format!("Unknown neighbourhood: {}", s)),
+        }
+    }
 }
 
-fn part_2(input: &str) -> usize {
-    let mut set = parse_input(input);
+/// What lies beyond the edges of the grid, including above the top and below the bottom layer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Boundary {
+    /// Empty space.
+    Open,
+    /// The opposite edge, as on a torus in every direction. The grid has to be wider than the
+    /// neighbourhood in every direction it wraps, so that no roll is its own neighbour, and
+    /// hexagonal neighbourhoods need an even number of rows to wrap consistently.
+    Toroidal,
+    /// Walls, which count as neighbouring rolls that are never taken away.
+    Walls,
+}
 
-    let count_before = set.len();
-    while take_removable_rolls(&mut set) {
-        continue;
+impl std::str::FromStr for Boundary {
+    type Err = String;
+
+    /// `open`, `torus` or `walls`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "open" => Ok(Boundary::Open),
+            "torus" => Ok(Boundary::Toroidal),
+            "walls" => Ok(Boundary::Walls),
+            _ => Err(format!("Unknown boundary: {}", s)),
+        }
     }
-    let count_after = set.len();
-    count_before - count_after
 }
 
-fn take_removable_rolls(set: &mut HashSet<(usize, usize)>) -> bool {
-    let mut removable_rolls = Vec::new();
-    for &(x, y) in set.iter() {
-        let mut adjecent_count = 0;
-        for dx in -1isize..=1 {
-            for dy in -1isize..=1 {
-                if dx == 0 && dy == 0 {
-                    continue;
+/// When a roll can be taken away.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Rules {
+    neighbourhood: Neighbourhood,
+    /// A roll is removable if fewer than this many of its neighbours are rolls.
+    threshold: u32,
+    boundary: Boundary,
+}
+
+impl Default for Rules {
+    fn default() -> Self {
+        Rules {
+            neighbourhood: Neighbourhood::Moore { radius: 1 },
+            threshold: 4,
+            boundary: Boundary::Open,
+        }
+    }
+}
+
+/// A fixed number of bits, one for every cell of a grid.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct BitSet {
+    words: Vec<u64>,
+}
+
+impl BitSet {
+    fn new(len: usize) -> Self {
+        BitSet {
+            words: vec![0; len.div_ceil(64)],
+        }
+    }
+
+    fn contains(&self, i: usize) -> bool {
+        self.words[i / 64] & (1 << (i % 64)) != 0
+    }
+
+    fn insert(&mut self, i: usize) {
+        self.words[i / 64] |= 1 << (i % 64);
+    }
+
+    fn remove(&mut self, i: usize) {
+        self.words[i / 64] &= !(1 << (i % 64));
+    }
+
+    fn len(&self) -> usize {
+        self.words
+            .iter()
+            .map(|word| word.count_ones() as usize)
+            .sum()
+    }
+
+    fn iter(&self) -> impl Iterator<Item = usize> + '_ {
+        self.words.iter().enumerate().flat_map(|(i, &word)| {
+            (0..64)
+                .filter(move |bit| word & (1 << bit) != 0)
+                .map(move |bit| i * 64 + bit)
+        })
+    }
+}
+
+/// The rolls on a dense grid of one or more layers, with the number of neighbouring rolls of every
+/// cell kept up to date as rolls are taken away.
+#[derive(Debug, Clone)]
+struct Grid {
+    width: usize,
+    height: usize,
+    depth: usize,
+    rules: Rules,
+    /// Neighbour offsets for even and odd rows, which only differ for hexagonal grids.
+    offsets: [Vec<(isize, isize, isize)>; 2],
+    /// Cell `x + (y + z * height) * width` is set if there is a roll.
+    rolls: BitSet,
+    neighbour_counts: Vec<u32>,
+    /// Rolls whose neighbour count changed since they were last checked.
+    worklist: Vec<usize>,
+    /// Cells currently on the worklist.
+    queued: BitSet,
+}
+
+/// Parse one or more layers, from the bottom up, separated by blank lines.
+fn parse_input(input: &str, rules: Rules) -> Result<Grid, String> {
+    let mut layers: Vec<Vec<&str>> = vec![Vec::new()];
+    // Only trailing whitespace is dropped, leading spaces are empty cells like `.`
+    for line in input.lines().map(|line| line.trim_end()) {
+        if !line.is_empty() {
+            layers.last_mut().unwrap().push(line);
+        } else if !layers.last().unwrap().is_empty() {
+            layers.push(Vec::new());
+        }
+    }
+    if layers.len() > 1 && layers.last().unwrap().is_empty() {
+        layers.pop();
+    }
+    let lines = || layers.iter().flatten();
+    let width = lines().map(|line| line.len()).max().unwrap_or(0);
+    let height = layers.iter().map(|layer| layer.len()).max().unwrap_or(0);
+    let depth = layers.len();
+
+    let mut rolls = BitSet::new(width * height * depth);
+    for (z, layer) in layers.iter().enumerate() {
+        for (y, line) in layer.iter().enumerate() {
+            for (x, c) in line.chars().enumerate() {
+                if c == '@' {
+                    rolls.insert(x + (y + z * height) * width);
                 }
-                if dx == -1 && x == 0 {
-                    continue;
+            }
+        }
+    }
+    Grid::new(width, height, depth, rolls, rules)
+}
+
+impl Grid {
+    /// Fails for toroidal grids the neighbourhood cannot wrap around, see [`Boundary::Toroidal`].
+    fn new(
+        width: usize,
+        height: usize,
+        depth: usize,
+        rolls: BitSet,
+        rules: Rules,
+    ) -> Result<Self, String> {
+        let offsets = [
+            rules.neighbourhood.offsets(0),
+            rules.neighbourhood.offsets(1),
+        ];
+        if rules.boundary == Boundary::Toroidal {
+            if rules.neighbourhood == Neighbourhood::Hexagonal && !height.is_multiple_of(2) {
+                return Err(format!(
+                    "A hexagonal torus needs an even number of rows, not {}",
+                    height
+                ));
+            }
+            // Reaching `reach` cells both ways wraps back onto the cell itself on smaller grids
+            let offsets = offsets.iter().flatten();
+            let reach = |axis: fn(&(isize, isize, isize)) -> isize| {
+                offsets
+                    .clone()
+                    .map(|offset| axis(offset).unsigned_abs())
+                    .max()
+                    .unwrap_or(0)
+            };
+            let reaches = [reach(|o| o.0), reach(|o| o.1), reach(|o| o.2)];
+            for (size, reach) in [width, height, depth].into_iter().zip(reaches) {
+                if reach > 0 && size < 2 * reach + 1 {
+                    return Err(format!(
+                        "A {}x{}x{} torus is too small for {:?}",
+                        width, height, depth, rules.neighbourhood
+                    ));
                 }
-                if dy == -1 && y == 0 {
-                    continue;
+            }
+        }
+
+        let mut grid = Grid {
+            width,
+            height,
+            depth,
+            rules,
+            offsets,
+            neighbour_counts: vec![0; width * height * depth],
+            worklist: rolls.iter().collect(),
+            queued: rolls.clone(),
+            rolls,
+        };
+        for cell in grid.rolls.iter() {
+            for neighbour in grid.neighbours(cell).collect::<Vec<_>>() {
+                grid.neighbour_counts[neighbour] += 1;
+            }
+        }
+        if rules.boundary == Boundary::Walls {
+            for cell in 0..width * height * depth {
+                let (_, y, _) = grid.coordinates(cell);
+                let inside = grid.neighbours(cell).count();
+                let outside = grid.offsets[y % 2].len() - inside;
+                grid.neighbour_counts[cell] += outside as u32;
+            }
+        }
+        Ok(grid)
+    }
+
+    fn roll_count(&self) -> usize {
+        self.rolls.len()
+    }
+
+    /// The position `(x, y, z)` of a cell.
+    fn coordinates(&self, cell: usize) -> (usize, usize, usize) {
+        let (x, row) = (cell % self.width, cell / self.width);
+        (x, row % self.height, row / self.height)
+    }
+
+    /// The cells of the grid around `cell` in the neighbourhood. Positions beyond the edges are
+    /// left out, unless the boundary wraps around.
+    fn neighbours(&self, cell: usize) -> impl Iterator<Item = usize> + '_ {
+        let (x, y, z) = self.coordinates(cell);
+        let (width, height, depth) = (self.width, self.height, self.depth);
+        let toroidal = self.rules.boundary == Boundary::Toroidal;
+        let step = move |position: usize, offset: isize, size: usize| {
+            if toroidal {
+                Some((position as isize + offset).rem_euclid(size as isize) as usize)
+            } else {
+                position
+                    .checked_add_signed(offset)
+                    .filter(|&new| new < size)
+            }
+        };
+        self.offsets[y % 2].iter().filter_map(move |&(dx, dy, dz)| {
+            let new_x = step(x, dx, width)?;
+            let new_y = step(y, dy, height)?;
+            let new_z = step(z, dz, depth)?;
+            Some(new_x + (new_y + new_z * height) * width)
+        })
+    }
+
+    /// The number of the given cells in every layer, from the bottom up.
+    fn count_per_layer(&self, cells: impl IntoIterator<Item = usize>) -> Vec<usize> {
+        let mut counts = vec![0; self.depth];
+        for cell in cells {
+            counts[self.coordinates(cell).2] += 1;
+        }
+        counts
+    }
+
+    /// Take away all rolls with fewer neighbouring rolls than the threshold at once, and return
+    /// them in ascending order.
+    ///
+    /// Only rolls on the worklist can have become removable since the last call, so the work is
+    /// proportional to the number of rolls removed (and their neighbours), not to all rolls.
+    fn take_removable_rolls(&mut self) -> Vec<usize> {
+        let candidates = std::mem::take(&mut self.worklist);
+        for &cell in &candidates {
+            self.queued.remove(cell);
+        }
+        let mut removable_rolls: Vec<usize> = candidates
+            .into_iter()
+            .filter(|&cell| self.neighbour_counts[cell] < self.rules.threshold)
+            .collect();
+        removable_rolls.sort_unstable();
+
+        for &cell in &removable_rolls {
+            self.rolls.remove(cell);
+        }
+        for &cell in &removable_rolls {
+            for neighbour in self.neighbours(cell).collect::<Vec<_>>() {
+                self.neighbour_counts[neighbour] -= 1;
+                if self.rolls.contains(neighbour) && !self.queued.contains(neighbour) {
+                    self.queued.insert(neighbour);
+                    self.worklist.push(neighbour);
                 }
-                let (new_x, new_y) = (x as isize + dx, y as isize + dy);
-                if set.contains(&(new_x as usize, new_y as usize)) {
-                    adjecent_count += 1;
+            }
+        }
+        removable_rolls
+    }
+}
+
+/// Take away removable rolls round after round until none are left, and return the rolls removed
+/// in every round.
+fn removal_rounds(grid: &mut Grid) -> Vec<Vec<usize>> {
+    let mut rounds = Vec::new();
+    loop {
+        let removed = grid.take_removable_rolls();
+        if removed.is_empty() {
+            return rounds;
+        }
+        rounds.push(removed);
+    }
+}
+
+/// The connected groups of rolls on a grid, where rolls are connected if they are neighbours.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Components {
+    /// The component of every cell with a roll, numbered by their first cell.
+    labels: Vec<Option<usize>>,
+    /// The number of rolls in every component.
+    sizes: Vec<usize>,
+}
+
+impl Grid {
+    /// Find the components of the rolls currently on the grid. After [`removal_rounds`] these are
+    /// the parts of the stable core, the rolls that can never be taken away.
+    fn components(&self) -> Components {
+        let mut labels = vec![None; self.neighbour_counts.len()];
+        let mut sizes = Vec::new();
+        for start in self.rolls.iter() {
+            if labels[start].is_some() {
+                continue;
+            }
+            let label = sizes.len();
+            labels[start] = Some(label);
+            let mut size = 0;
+            let mut stack = vec![start];
+            while let Some(cell) = stack.pop() {
+                size += 1;
+                for neighbour in self.neighbours(cell) {
+                    if self.rolls.contains(neighbour) && labels[neighbour].is_none() {
+                        labels[neighbour] = Some(label);
+                        stack.push(neighbour);
+                    }
                 }
             }
+            sizes.push(size);
         }
-        if adjecent_count < 4 {
-            removable_rolls.push((x, y));
+        Components { labels, sizes }
+    }
+}
+
+impl Components {
+    /// The component number of every roll, right-aligned in columns, with empty cells shown as
+    /// `.` and layers separated by blank lines.
+    fn labelled_grid(&self, width: usize, height: usize) -> String {
+        let column_width = self.sizes.len().saturating_sub(1).to_string().len();
+        let layers: Vec<String> = self
+            .labels
+            .chunks((width * height).max(1))
+            .map(|layer| {
+                let mut output = String::new();
+                for row in layer.chunks(width) {
+                    let labels: Vec<String> = row
+                        .iter()
+                        .map(|label| match label {
+                            Some(label) => format!("{:>column_width$}", label),
+                            None => format!("{:>column_width$}", "."),
+                        })
+                        .collect();
+                    output.push_str(&labels.join(" "));
+                    output.push('\n');
+                }
+                output
+            })
+            .collect();
+        layers.join("\n")
+    }
+}
+
+/// What happened to a cell while rolls were taken away.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Fate {
+    /// There never was a roll.
+    Empty,
+    /// The roll was taken away in this round, counting from 1.
+    Removed { round: usize },
+    /// The roll was never taken away.
+    Remaining,
+}
+
+/// The round every roll of a grid was taken away in.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Timeline {
+    width: usize,
+    height: usize,
+    depth: usize,
+    fates: Vec<Fate>,
+    round_count: usize,
+}
+
+/// Pixels per cell side in exported images.
+const IMAGE_SCALE: usize = 4;
+
+impl Timeline {
+    /// Combine the grid before any removal with the rolls removed in every round, as returned by
+    /// [`removal_rounds`].
+    fn new(initial_grid: &Grid, rounds: &[Vec<usize>]) -> Self {
+        let mut fates = vec![Fate::Empty; initial_grid.neighbour_counts.len()];
+        for cell in initial_grid.rolls.iter() {
+            fates[cell] = Fate::Remaining;
+        }
+        for (round, removed) in rounds.iter().enumerate() {
+            for &cell in removed {
+                fates[cell] = Fate::Removed { round: round + 1 };
+            }
+        }
+        Timeline {
+            width: initial_grid.width,
+            height: initial_grid.height,
+            depth: initial_grid.depth,
+            fates,
+            round_count: rounds.len(),
         }
     }
-    for roll in &removable_rolls {
-        set.remove(roll);
+
+    /// The fates of every layer, from the bottom up.
+    fn layers(&self) -> impl Iterator<Item = &[Fate]> {
+        self.fates.chunks((self.width * self.height).max(1))
     }
-    !removable_rolls.is_empty()
+
+    /// The round number of every cell, right-aligned in columns, with layers separated by blank
+    /// lines. Empty cells are shown as `.`, rolls that were never removed as `@`.
+    fn labelled_grid(&self) -> String {
+        let column_width = self.round_count.max(1).to_string().len();
+        let layers: Vec<String> = self
+            .layers()
+            .map(|layer| {
+                let mut output = String::new();
+                for row in layer.chunks(self.width) {
+                    let labels: Vec<String> = row
+                        .iter()
+                        .map(|fate| match fate {
+                            Fate::Empty => format!("{:>column_width$}", "."),
+                            Fate::Removed { round } => format!("{:>column_width$}", round),
+                            Fate::Remaining => format!("{:>column_width$}", "@"),
+                        })
+                        .collect();
+                    output.push_str(&labels.join(" "));
+                    output.push('\n');
+                }
+                output
+            })
+            .collect();
+        layers.join("\n")
+    }
+
+    /// Whether there is still a roll in the cell after `rounds` rounds.
+    fn has_roll_after(&self, cell: usize, rounds: usize) -> bool {
+        match self.fates[cell] {
+            Fate::Empty => false,
+            Fate::Removed { round } => round > rounds,
+            Fate::Remaining => true,
+        }
+    }
+
+    /// The grid in the input format, layers separated by blank lines, before the first round and
+    /// after every round.
+    fn frames(&self) -> Vec<String> {
+        (0..=self.round_count)
+            .map(|rounds| {
+                let layers: Vec<String> = (0..self.depth)
+                    .map(|z| {
+                        let mut layer = String::new();
+                        for y in 0..self.height {
+                            for x in 0..self.width {
+                                let cell = x + (y + z * self.height) * self.width;
+                                let has_roll = self.has_roll_after(cell, rounds);
+                                layer.push(if has_roll { '@' } else { '.' });
+                            }
+                            layer.push('\n');
+                        }
+                        layer
+                    })
+                    .collect();
+                layers.join("\n")
+            })
+            .collect()
+    }
+
+    /// Every roll coloured by its removal round, from red for the first round to blue for the
+    /// last. Rolls that were never removed are black, empty cells white.
+    fn image(&self, scale: usize) -> Image {
+        self.render(scale, |fate| match fate {
+            Fate::Empty => [255, 255, 255],
+            Fate::Removed { round } => {
+                let t = (round - 1) as f64 / (self.round_count.max(2) - 1) as f64;
+                [(255.0 * (1.0 - t)) as u8, 64, (255.0 * t) as u8]
+            }
+            Fate::Remaining => [0, 0, 0],
+        })
+    }
+
+    /// The rolls left after `rounds` rounds in grey, with those removed in that very round
+    /// highlighted in red.
+    fn frame_image(&self, rounds: usize, scale: usize) -> Image {
+        self.render(scale, |fate| match fate {
+            Fate::Removed { round } if round == rounds => [255, 0, 0],
+            Fate::Removed { round } if round > rounds => [96, 96, 96],
+            Fate::Remaining => [96, 96, 96],
+            _ => [255, 255, 255],
+        })
+    }
+
+    /// Layers are stacked from top to bottom, with a blank row of cells between them.
+    fn render(&self, scale: usize, colour: impl Fn(Fate) -> [u8; 3]) -> Image {
+        let rows = self.height * self.depth + self.depth.saturating_sub(1);
+        let mut image = Image {
+            width: self.width * scale,
+            height: rows * scale,
+            pixels: Vec::with_capacity(self.width * rows * scale * scale),
+        };
+        for (z, layer) in self.layers().enumerate() {
+            if z > 0 {
+                let gap = self.width * scale * scale;
+                image
+                    .pixels
+                    .extend(std::iter::repeat_n(colour(Fate::Empty), gap));
+            }
+            for row in layer.chunks(self.width) {
+                let row_pixels: Vec<[u8; 3]> = row
+                    .iter()
+                    .flat_map(|&fate| std::iter::repeat_n(colour(fate), scale))
+                    .collect();
+                for _ in 0..scale {
+                    image.pixels.extend_from_slice(&row_pixels);
+                }
+            }
+        }
+        image
+    }
+}
+
+/// An RGB image, row by row.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Image {
+    width: usize,
+    height: usize,
+    pixels: Vec<[u8; 3]>,
+}
+
+impl Image {
+    /// Binary PPM (P6).
+    fn to_ppm(&self) -> Vec<u8> {
+        let mut ppm = format!("P6\n{} {}\n255\n", self.width, self.height).into_bytes();
+        ppm.extend(self.pixels.iter().flatten());
+        ppm
+    }
+
+    /// PNG without compression: the pixel data is stored in uncompressed deflate blocks, which
+    /// every PNG reader understands and needs no compression library.
+    fn to_png(&self) -> Vec<u8> {
+        // Every row starts with filter type 0 (none)
+        let mut raw = Vec::with_capacity(self.height * (1 + 3 * self.width));
+        for row in self.pixels.chunks(self.width.max(1)) {
+            raw.push(0);
+            raw.extend(row.iter().flatten());
+        }
+
+        // zlib stream: header, stored blocks of at most 65535 bytes, Adler-32 checksum
+        let mut zlib = vec![0x78, 0x01];
+        let mut blocks = raw.chunks(65535).peekable();
+        if blocks.peek().is_none() {
+            zlib.extend([1, 0, 0, 0xFF, 0xFF]);
+        }
+        while let Some(block) = blocks.next() {
+            let is_last = blocks.peek().is_none();
+            zlib.push(is_last as u8);
+            zlib.extend((block.len() as u16).to_le_bytes());
+            zlib.extend((!(block.len() as u16)).to_le_bytes());
+            zlib.extend(block);
+        }
+        zlib.extend(adler32(&raw).to_be_bytes());
+
+        let mut header = Vec::with_capacity(13);
+        header.extend((self.width as u32).to_be_bytes());
+        header.extend((self.height as u32).to_be_bytes());
+        // 8 bits per channel, RGB, default compression, filtering and no interlacing
+        header.extend([8, 2, 0, 0, 0]);
+
+        let mut png = vec![0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1A, b'\n'];
+        push_png_chunk(&mut png, b"IHDR", &header);
+        push_png_chunk(&mut png, b"IDAT", &zlib);
+        push_png_chunk(&mut png, b"IEND", &[]);
+        png
+    }
+}
+
+fn push_png_chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    png.extend((data.len() as u32).to_be_bytes());
+    png.extend(kind);
+    png.extend(data);
+    let checksum = crc32(kind.iter().chain(data).copied());
+    png.extend(checksum.to_be_bytes());
+}
+
+/// CRC-32 as used by PNG (polynomial 0xEDB88320, reflected).
+fn crc32(bytes: impl IntoIterator<Item = u8>) -> u32 {
+    let mut crc = 0xFFFFFFFFu32;
+    for byte in bytes {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xEDB88320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+/// Adler-32 as used by zlib.
+fn adler32(bytes: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for &byte in bytes {
+        a = (a + byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    (b << 16) | a
+}
+
+fn part_1(input: &str) -> usize {
+    let mut grid = parse_input(input, Rules::default()).expect("The default rules fit any grid");
+
+    let count_before = grid.roll_count();
+    grid.take_removable_rolls();
+    let count_after = grid.roll_count();
+    count_before - count_after
+}
+
+fn part_2(input: &str) -> usize {
+    let mut grid = parse_input(input, Rules::default()).expect("The default rules fit any grid");
+
+    let count_before = grid.roll_count();
+    removal_rounds(&mut grid);
+    let count_after = grid.roll_count();
+    count_before - count_after
 }
 
 #[cfg(test)]
@@ -75,19 +804,433 @@ mod tests {
     use super::*;
 
     #[test]
-    fn test_part_1_simple() {
+    fn test_bit_set() {
+        let mut set = BitSet::new(130);
+        set.insert(0);
+        set.insert(64);
+        set.insert(129);
+        assert!(set.contains(64));
+        assert!(!set.contains(63));
+        assert_eq!(set.len(), 3);
+        set.remove(64);
+        assert_eq!(set.iter().collect::<Vec<_>>(), vec![0, 129]);
+    }
+
+    #[test]
+    fn test_parse_input() {
+        let grid = parse_input(
+            "\
+@@.
+.@@
+",
+            Rules::default(),
+        )
+        .unwrap();
+        assert_eq!((grid.width, grid.height), (3, 2));
+        assert_eq!(grid.rolls.iter().collect::<Vec<_>>(), vec![0, 1, 4, 5]);
+        assert_eq!(grid.neighbour_counts, vec![2, 3, 3, 3, 3, 2]);
+
+        // Leading spaces are empty cells, trailing ones are ignored
+        let grid = parse_input("  @\n@@@  \n", Rules::default()).unwrap();
+        assert_eq!((grid.width, grid.height), (3, 2));
+        assert_eq!(grid.rolls.iter().collect::<Vec<_>>(), vec![2, 3, 4, 5]);
+    }
+
+    #[test]
+    fn test_take_removable_rolls_in_rounds() {
+        // The centre only becomes removable once the corners are gone
+        let mut grid = parse_input(
+            "\
+@.@
+.@.
+@.@
+",
+            Rules::default(),
+        )
+        .unwrap();
+        assert_eq!(grid.take_removable_rolls(), vec![0, 2, 6, 8]);
+        assert_eq!(grid.take_removable_rolls(), vec![4]);
+        assert_eq!(grid.take_removable_rolls(), vec![]);
+
+        let mut grid = parse_input(
+            "\
+@@@
+@@@
+@@@
+",
+            Rules::default(),
+        )
+        .unwrap();
+        assert_eq!(grid.take_removable_rolls(), vec![0, 2, 6, 8]);
+        assert_eq!(grid.take_removable_rolls(), vec![1, 3, 5, 7]);
+        assert_eq!(grid.take_removable_rolls(), vec![4]);
+        assert_eq!(grid.roll_count(), 0);
+    }
+
+    #[test]
+    fn test_neighbourhood_offsets() {
+        assert_eq!(Neighbourhood::VonNeumann.offsets(0).len(), 4);
+        assert_eq!(Neighbourhood::Moore { radius: 1 }.offsets(0).len(), 8);
+        assert_eq!(Neighbourhood::Moore { radius: 2 }.offsets(0).len(), 24);
+        assert_eq!(Neighbourhood::Hexagonal.offsets(0).len(), 6);
+        assert!(Neighbourhood::Hexagonal.offsets(0).contains(&(-1, 1, 0)));
+        assert!(Neighbourhood::Hexagonal.offsets(1).contains(&(1, 1, 0)));
+        assert_eq!(Neighbourhood::Faces.offsets(0).len(), 6);
+        assert_eq!(Neighbourhood::Cube.offsets(0).len(), 26);
+        assert_eq!("26".parse(), Ok(Neighbourhood::Cube));
+        assert_eq!("4".parse(), Ok(Neighbourhood::VonNeumann));
+        assert_eq!("moore:3".parse(), Ok(Neighbourhood::Moore { radius: 3 }));
+        assert_eq!("hex".parse(), Ok(Neighbourhood::Hexagonal));
+        assert!("moore:x".parse::<Neighbourhood>().is_err());
+    }
+
+    #[test]
+    fn test_rules() {
+        let input = "\
+@@@
+@@@
+@@@
+";
+        // 4-connected: corners have 2 neighbours, edges 3, the centre 4
+        let rules = Rules {
+            neighbourhood: Neighbourhood::VonNeumann,
+            threshold: 3,
+            ..Default::default()
+        };
+        let mut grid = parse_input(input, rules).unwrap();
+        assert_eq!(grid.take_removable_rolls(), vec![0, 2, 6, 8]);
+        assert_eq!(grid.take_removable_rolls(), vec![1, 3, 5, 7]);
+        assert_eq!(grid.take_removable_rolls(), vec![4]);
+
+        // Radius 2 reaches the whole grid, so every roll has 8 neighbours
+        let rules = Rules {
+            neighbourhood: Neighbourhood::Moore { radius: 2 },
+            threshold: 8,
+            ..Default::default()
+        };
+        assert_eq!(
+            removal_rounds(&mut parse_input(input, rules).unwrap()).len(),
+            0
+        );
+        let rules = Rules {
+            neighbourhood: Neighbourhood::Moore { radius: 2 },
+            threshold: 9,
+            ..Default::default()
+        };
+        assert_eq!(
+            removal_rounds(&mut parse_input(input, rules).unwrap()),
+            vec![(0..9).collect::<Vec<_>>()]
+        );
+
+        // Odd rows are shifted right: cell 4 touches 1, 2 above and 7, 8 below
+        let rules = Rules {
+            neighbourhood: Neighbourhood::Hexagonal,
+            threshold: 4,
+            ..Default::default()
+        };
+        let grid = parse_input(input, rules).unwrap();
+        assert_eq!(grid.neighbour_counts, vec![2, 4, 3, 5, 6, 3, 2, 4, 3]);
+    }
+
+    #[test]
+    fn test_boundary() {
+        let input = "\
+@@.
+@@.
+...
+";
+        let counts = |boundary| {
+            let rules = Rules {
+                boundary,
+                ..Default::default()
+            };
+            parse_input(input, rules).unwrap().neighbour_counts
+        };
+        assert_eq!(counts(Boundary::Open), vec![3, 3, 2, 3, 3, 2, 2, 2, 1]);
+        // On a 3x3 torus every cell sees all others
+        assert_eq!(counts(Boundary::Toroidal), vec![3, 3, 4, 3, 3, 4, 4, 4, 4]);
+        // Corners have 5 wall positions around them, edges 3
+        assert_eq!(counts(Boundary::Walls), vec![8, 6, 7, 6, 3, 5, 7, 5, 6]);
+
+        let walls = Rules {
+            boundary: Boundary::Walls,
+            ..Default::default()
+        };
+        let mut grid = parse_input("@", walls).unwrap();
+        assert_eq!(grid.take_removable_rolls(), vec![]);
+        let mut grid = parse_input("@", Rules::default()).unwrap();
+        assert_eq!(grid.take_removable_rolls(), vec![0]);
+
+        assert_eq!("torus".parse(), Ok(Boundary::Toroidal));
+
+        // Tori the neighbourhood wraps around onto itself are rejected
+        let torus = |neighbourhood| Rules {
+            neighbourhood,
+            boundary: Boundary::Toroidal,
+            ..Default::default()
+        };
+        assert!(parse_input("@@\n@@\n@@", torus(Neighbourhood::Hexagonal)).is_err());
+        assert!(parse_input("@@\n@@", torus(Neighbourhood::Hexagonal)).is_err());
+        assert!(parse_input("@@@\n@@@\n@@@\n@@@", torus(Neighbourhood::Hexagonal)).is_ok());
+        assert!(parse_input("@", torus(Neighbourhood::Cube)).is_err());
+        assert!(parse_input("@@@\n@@@\n@@@", torus(Neighbourhood::Moore { radius: 2 })).is_err());
+        assert!(parse_input("@@@@", torus(Neighbourhood::VonNeumann)).is_err());
+        assert!("sphere".parse::<Boundary>().is_err());
+    }
+
+    #[test]
+    fn test_toroidal_removal() {
+        // A full ring of rolls along the edges of a torus is one closed band, where every roll
+        // has at least 4 neighbours, but with open edges it erodes from the corners
+        let input = "\
+@@@@@
+@...@
+@...@
+@@@@@
+";
+        let torus = Rules {
+            boundary: Boundary::Toroidal,
+            ..Default::default()
+        };
+        assert_eq!(
+            removal_rounds(&mut parse_input(input, torus).unwrap()).len(),
+            0
+        );
+        assert!(!removal_rounds(&mut parse_input(input, Rules::default()).unwrap()).is_empty());
+    }
+
+    #[test]
+    fn test_parse_layers() {
         let input = "
-            ..@@.@@@@.
-            @@@.@.@.@@
-            @@@@@.@.@@
-            @.@@@@..@.
-            @@.@@@@.@@
-            .@@@@@@@.@
-            .@.@.@.@@@
-            @.@@@.@@@@
-            .@@@@@@@@.
-            @.@.@@@.@.
-            ";
+
+@@
+@.
+
+.@
+@@
+@@
+
+
+";
+        let grid = parse_input(input, Rules::default()).unwrap();
+        assert_eq!((grid.width, grid.height, grid.depth), (2, 3, 2));
+        assert_eq!(
+            grid.rolls.iter().collect::<Vec<_>>(),
+            vec![0, 1, 2, 7, 8, 9, 10, 11]
+        );
+        assert_eq!(grid.coordinates(9), (1, 1, 1));
+        assert_eq!(grid.count_per_layer(grid.rolls.iter()), vec![3, 5]);
+    }
+
+    #[test]
+    fn test_layers() {
+        let input = "\
+@@@
+@@@
+@@@
+
+@@@
+@@@
+@@@
+";
+        // 2D neighbourhoods treat every layer on its own
+        let mut grid = parse_input(input, Rules::default()).unwrap();
+        assert_eq!(grid.take_removable_rolls(), vec![0, 2, 6, 8, 9, 11, 15, 17]);
+
+        // With 6 neighbours the corners have 3, edges 4 and centres 5
+        let rules = Rules {
+            neighbourhood: Neighbourhood::Faces,
+            threshold: 4,
+            ..Default::default()
+        };
+        let mut grid = parse_input(input, rules).unwrap();
+        assert_eq!(grid.neighbour_counts[..9], [3, 4, 3, 4, 5, 4, 3, 4, 3]);
+        assert_eq!(grid.take_removable_rolls(), vec![0, 2, 6, 8, 9, 11, 15, 17]);
+
+        // With 26 neighbours the corners have 7, edges 11 and centres 17
+        let rules = Rules {
+            neighbourhood: Neighbourhood::Cube,
+            threshold: 12,
+            ..Default::default()
+        };
+        let mut grid = parse_input(input, rules).unwrap();
+        assert_eq!(grid.neighbour_counts[..9], [7, 11, 7, 11, 17, 11, 7, 11, 7]);
+        let rounds = removal_rounds(&mut grid);
+        assert_eq!(rounds.len(), 2);
+        assert_eq!(
+            grid.count_per_layer(rounds.iter().flatten().copied()),
+            vec![9, 9]
+        );
+    }
+
+    #[test]
+    fn test_stable_core_components() {
+        // Two solid blocks stay, the loose rolls around them go
+        let input = "\
+@@@..@@.
+@@@..@@@
+.....@..
+@.@.....
+";
+        let rules = Rules {
+            threshold: 3,
+            ..Default::default()
+        };
+        let mut grid = parse_input(input, rules).unwrap();
+        removal_rounds(&mut grid);
+        let components = grid.components();
+        assert_eq!(components.sizes, vec![6, 4]);
+        assert_eq!(
+            components.labelled_grid(grid.width, grid.height),
+            "\
+0 0 0 . . 1 1 .
+0 0 0 . . 1 1 .
+. . . . . . . .
+. . . . . . . .
+"
+        );
+
+        // Everything goes with the default rules
+        let mut grid = parse_input("@@\n@@", Rules::default()).unwrap();
+        removal_rounds(&mut grid);
+        assert_eq!(grid.components().sizes, vec![]);
+
+        let grid = parse_input("", Rules::default()).unwrap();
+        assert_eq!(grid.components().labelled_grid(grid.width, grid.height), "");
+    }
+
+    fn example_timeline() -> Timeline {
+        let mut grid = parse_input(
+            "\
+@.@
+.@.
+@.@
+@@.
+",
+            Rules::default(),
+        )
+        .unwrap();
+        let initial_grid = grid.clone();
+        let rounds = removal_rounds(&mut grid);
+        Timeline::new(&initial_grid, &rounds)
+    }
+
+    #[test]
+    fn test_timeline() {
+        let timeline = example_timeline();
+        assert_eq!(timeline.round_count, 2);
+        assert_eq!(timeline.fates[0], Fate::Removed { round: 1 });
+        assert_eq!(timeline.fates[1], Fate::Empty);
+        assert_eq!(timeline.labelled_grid(), "1 . 1\n. 2 .\n1 . 1\n1 1 .\n");
+        assert_eq!(
+            timeline.frames(),
+            vec![
+                "@.@\n.@.\n@.@\n@@.\n",
+                "...\n.@.\n...\n...\n",
+                "...\n...\n...\n...\n",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_timeline_remaining() {
+        let mut grid = parse_input(
+            "\
+@@@
+@@@
+@@@
+",
+            Rules {
+                neighbourhood: Neighbourhood::Moore { radius: 1 },
+                threshold: 3,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let initial_grid = grid.clone();
+        let rounds = removal_rounds(&mut grid);
+        let timeline = Timeline::new(&initial_grid, &rounds);
+        assert_eq!(timeline.round_count, 0);
+        assert_eq!(timeline.labelled_grid(), "@ @ @\n@ @ @\n@ @ @\n");
+    }
+
+    #[test]
+    fn test_timeline_layers() {
+        let mut grid = parse_input(
+            "\
+@@
+@@
+
+@.
+..
+",
+            Rules::default(),
+        )
+        .unwrap();
+        let initial_grid = grid.clone();
+        let rounds = removal_rounds(&mut grid);
+        let timeline = Timeline::new(&initial_grid, &rounds);
+        assert_eq!(timeline.labelled_grid(), "1 1\n1 1\n\n1 .\n. .\n");
+        assert_eq!(
+            timeline.frames(),
+            vec!["@@\n@@\n\n@.\n..\n", "..\n..\n\n..\n..\n"]
+        );
+        let image = timeline.image(1);
+        assert_eq!((image.width, image.height), (2, 5));
+        assert_eq!(image.pixels[4], [255, 255, 255]);
+        assert_eq!(image.pixels[6], [255, 64, 0]);
+        assert_eq!(timeline.frame_image(1, 2).pixels.len(), 4 * 10);
+    }
+
+    #[test]
+    fn test_timeline_images() {
+        let timeline = example_timeline();
+        let image = timeline.image(2);
+        assert_eq!((image.width, image.height), (6, 8));
+        assert_eq!(image.pixels[0], [255, 64, 0]);
+        assert_eq!(image.pixels[2], [255, 255, 255]);
+        assert_eq!(image.pixels[6 * 2 + 2], [0, 64, 255]);
+
+        let frame = timeline.frame_image(1, 1);
+        assert_eq!(frame.pixels[0], [255, 0, 0]);
+        assert_eq!(frame.pixels[4], [96, 96, 96]);
+
+        let ppm = image.to_ppm();
+        assert!(ppm.starts_with(b"P6\n6 8\n255\n"));
+        assert_eq!(ppm.len(), b"P6\n6 8\n255\n".len() + 6 * 8 * 3);
+    }
+
+    #[test]
+    fn test_png() {
+        let png = example_timeline().image(1).to_png();
+        assert!(png.starts_with(&[0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1A, b'\n']));
+        // IHDR: 3x4 pixels
+        assert_eq!(&png[12..16], b"IHDR");
+        assert_eq!(&png[16..24], &[0, 0, 0, 3, 0, 0, 0, 4]);
+        // The IEND chunk and its well-known checksum close the file
+        assert!(png.ends_with(&[0, 0, 0, 0, b'I', b'E', b'N', b'D', 0xAE, 0x42, 0x60, 0x82]));
+    }
+
+    #[test]
+    fn test_checksums() {
+        assert_eq!(crc32(*b"123456789"), 0xCBF43926);
+        assert_eq!(adler32(b"Wikipedia"), 0x11E60398);
+    }
+
+    #[test]
+    fn test_part_1_simple() {
+        let input = "\
+..@@.@@@@.
+@@@.@.@.@@
+@@@@@.@.@@
+@.@@@@..@.
+@@.@@@@.@@
+.@@@@@@@.@
+.@.@.@.@@@
+@.@@@.@@@@
+.@@@@@@@@.
+@.@.@@@.@.
+";
         assert_eq!(part_1(input), 13);
     }
 
@@ -98,18 +1241,18 @@ mod tests {
 
     #[test]
     fn test_part_2_simple() {
-        let input = "
-            ..@@.@@@@.
-            @@@.@.@.@@
-            @@@@@.@.@@
-            @.@@@@..@.
-            @@.@@@@.@@
-            .@@@@@@@.@
-            .@.@.@.@@@
-            @.@@@.@@@@
-            .@@@@@@@@.
-            @.@.@@@.@.
-            ";
+        let input = "\
+..@@.@@@@.
+@@@.@.@.@@
+@@@@@.@.@@
+@.@@@@..@.
+@@.@@@@.@@
+.@@@@@@@.@
+.@.@.@.@@@
+@.@@@.@@@@
+.@@@@@@@@.
+@.@.@@@.@.
+";
         assert_eq!(part_2(input), 43);
     }
 
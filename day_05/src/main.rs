@@ -1,70 +1,719 @@
 const INPUT: &str = include_str!("../input.txt");
 
+/// Lines read between flushes of the output in batch mode.
+const BATCH_CHUNK_LINES: usize = 64 * 1024;
+
 fn main() {
-    let (ranges, numbers) = parse_input(INPUT);
-    println!("Part 1: {}", part_1(&ranges, &numbers));
-    println!("Part 2: {}", part_2(&ranges));
+    // Options: `--ranges <path>` for another range database, `--stdin` to answer IDs from stdin
+    // as they arrive, `--batch <path>` to answer a file of IDs, and `--filter <fresh|spoiled>` to
+    // print only the matching IDs instead of labelling every one. `--containing <id>` lists the
+    // ranges an ID is in and `--overlaps` shows where ranges overlap. Lines of the input that are
+    // left out are reported as warnings, or with `--strict` as errors. `--bounds half-open` reads
+    // `3-5` as 3 and 4 only, `--no-merge-adjacent` keeps touching ranges apart and `--list` prints
+    // the fresh ranges after merging and taking out the `!a-b` exclusions
+    let mut args = std::env::args().skip(1);
+    let mut input = INPUT.to_string();
+    let mut interactive = false;
+    let mut batch_path = None;
+    let mut filter = None;
+    let mut provenance_ids = Vec::new();
+    let mut show_overlaps = false;
+    let mut strict = false;
+    let mut bounds = Bounds::Inclusive;
+    let mut merge_adjacent = true;
+    let mut list = false;
+    while let Some(option) = args.next() {
+        match option.as_str() {
+            "--stdin" => interactive = true,
+            "--ranges" => {
+                let path = args.next().expect("Missing option value");
+                input = std::fs::read_to_string(path).expect("Could not read ranges");
+            }
+            "--batch" => batch_path = Some(args.next().expect("Missing option value")),
+            "--filter" => {
+                let value = args.next().expect("Missing option value");
+                filter = Some(value.parse().unwrap_or_else(|error| panic!("{}", error)));
+            }
+            "--containing" => {
+                let value = args.next().expect("Missing option value");
+                provenance_ids.push(value.parse::<usize>().expect("Invalid ID"));
+            }
+            "--overlaps" => show_overlaps = true,
+            "--strict" => strict = true,
+            "--bounds" => {
+                let value = args.next().expect("Missing option value");
+                bounds = value.parse().unwrap_or_else(|error| panic!("{}", error));
+            }
+            "--no-merge-adjacent" => merge_adjacent = false,
+            "--list" => list = true,
+            _ => panic!("Unknown option: {}", option),
+        }
+    }
+
+    let parsed = parse_input(&input, bounds);
+    let fresh = parsed.fresh_ids(merge_adjacent);
+    let ParsedInput {
+        ranges,
+        range_lines,
+        numbers,
+        warnings,
+        ..
+    } = parsed;
+    for warning in &warnings {
+        if strict {
+            eprintln!("Error: {}", warning);
+        } else {
+            eprintln!("Warning: {}", warning);
+        }
+    }
+    if strict && !warnings.is_empty() {
+        std::process::exit(1);
+    }
+    let answer_ids = batch_path.is_some() || interactive;
+    if provenance_ids.is_empty() && !show_overlaps && !answer_ids && !list {
+        println!("Part 1: {}", part_1(&fresh, &numbers));
+        println!("Part 2: {}", part_2(&fresh));
+        return;
+    }
+    if list {
+        for &range in fresh.intervals() {
+            println!("{}", bounds.format(range));
+        }
+    }
+    if !provenance_ids.is_empty() {
+        let tree = IntervalTree::new(&ranges);
+        for id in provenance_ids {
+            let containing: Vec<String> = tree
+                .containing(id)
+                .into_iter()
+                .map(|i| format!("line {} {}", range_lines[i], bounds.format(ranges[i])))
+                .collect();
+            if containing.is_empty() {
+                println!("{}: in no range", id);
+            } else {
+                println!("{}: {}", id, containing.join(", "));
+            }
+        }
+    }
+    if show_overlaps {
+        let segments = coverage(&ranges);
+        if let Some((point, multiplicity)) = maximum_overlap(&segments) {
+            println!("Maximum overlap: {} ranges at {}", multiplicity, point);
+        }
+        for segment in segments.iter().filter(|segment| segment.multiplicity > 1) {
+            let stretch = bounds.format((segment.start, segment.end));
+            println!("{}: {} ranges", stretch, segment.multiplicity);
+        }
+    }
+    if !answer_ids {
+        return;
+    }
+
+    let stdout = std::io::stdout().lock();
+    let (fresh_count, spoiled_count) = if let Some(path) = batch_path {
+        let file = std::fs::File::open(path).expect("Could not open batch file");
+        let reader = std::io::BufReader::with_capacity(1 << 20, file);
+        let writer = std::io::BufWriter::new(stdout);
+        answer_queries(&fresh, reader, writer, filter, BATCH_CHUNK_LINES)
+    } else {
+        answer_queries(&fresh, std::io::stdin().lock(), stdout, filter, 1)
+    }
+    .expect("Could not answer queries");
+    // On stderr, to keep stdout usable in pipelines
+    eprintln!("Fresh: {}, spoiled: {}", fresh_count, spoiled_count);
+}
+
+/// Whether an ingredient ID falls within any fresh range.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Freshness {
+    Fresh,
+    Spoiled,
+}
+
+impl std::fmt::Display for Freshness {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Freshness::Fresh => write!(f, "fresh"),
+            Freshness::Spoiled => write!(f, "spoiled"),
+        }
+    }
+}
+
+impl std::str::FromStr for Freshness {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "fresh" => Ok(Freshness::Fresh),
+            "spoiled" => Ok(Freshness::Spoiled),
+            _ => Err(format!("Unknown freshness: {}", s)),
+        }
+    }
+}
+
+/// Answer one ID per line from `reader`, writing `<id> fresh` or `<id> spoiled`, or only the IDs
+/// with the `filter` freshness. The output is flushed every `chunk_lines` lines, so with 1 every
+/// answer is available as soon as its ID was read. Blank lines are skipped, other lines that are
+/// not IDs are reported on stderr. Returns the number of fresh and spoiled IDs.
+fn answer_queries(
+    fresh: &IntervalSet,
+    reader: impl std::io::BufRead,
+    mut writer: impl std::io::Write,
+    filter: Option<Freshness>,
+    chunk_lines: usize,
+) -> std::io::Result<(usize, usize)> {
+    let (mut fresh_count, mut spoiled_count) = (0, 0);
+    for (i, line) in reader.lines().enumerate() {
+        let line = line?;
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let Ok(id) = line.parse::<usize>() else {
+            eprintln!("Not an ID: {}", line);
+            continue;
+        };
+        let freshness = if fresh.contains(id) {
+            fresh_count += 1;
+            Freshness::Fresh
+        } else {
+            spoiled_count += 1;
+            Freshness::Spoiled
+        };
+        match filter {
+            None => writeln!(writer, "{} {}", id, freshness)?,
+            Some(filter) if filter == freshness => writeln!(writer, "{}", id)?,
+            Some(_) => {}
+        }
+        if (i + 1).is_multiple_of(chunk_lines) {
+            writer.flush()?;
+        }
+    }
+    writer.flush()?;
+    Ok((fresh_count, spoiled_count))
+}
+
+/// How the ends of a range `a-b` in the input are read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Bounds {
+    /// From `a` to `b`, both included.
+    Inclusive,
+    /// From `a` up to but not including `b`.
+    HalfOpen,
+}
+
+impl Bounds {
+    /// Write an inclusive interval the way it would appear in the input.
+    fn format(&self, (start, end): (usize, usize)) -> String {
+        match self {
+            Bounds::Inclusive => format!("{}-{}", start, end),
+            // Computed wider, since the end of `usize::MAX - 0` cannot be written in a `usize`
+            Bounds::HalfOpen => format!("{}-{}", start, end as u128 + 1),
+        }
+    }
 }
 
-fn parse_input(input: &str) -> (Vec<(usize, usize)>, Vec<usize>) {
+impl std::str::FromStr for Bounds {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "inclusive" => Ok(Bounds::Inclusive),
+            "half-open" => Ok(Bounds::HalfOpen),
+            _ => Err(format!("Unknown bounds: {}", s)),
+        }
+    }
+}
+
+/// Why a line of the input was left out.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Rejection {
+    /// A range ending before it starts.
+    ReversedRange { start: usize, end: usize },
+    /// A number too large for a `usize`.
+    Overflow,
+    /// Neither a range nor an ID.
+    Garbage,
+    /// A range after the IDs have started.
+    SectionOrder,
+}
+
+/// A line of the input that was left out, with its line number counting from 1.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Warning {
+    line_number: usize,
+    line: String,
+    rejection: Rejection,
+}
+
+impl std::fmt::Display for Warning {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "line {}: ", self.line_number)?;
+        match self.rejection {
+            Rejection::ReversedRange { start, end } => {
+                write!(f, "range {}-{} ends before it starts", start, end)
+            }
+            Rejection::Overflow => write!(f, "number too large in {:?}", self.line),
+            Rejection::Garbage => write!(f, "not a range or an ID: {:?}", self.line),
+            Rejection::SectionOrder => write!(f, "range {:?} after the IDs", self.line),
+        }
+    }
+}
+
+/// The fresh ranges, the ranges excluded from them and the available IDs, with a warning for every
+/// line that was left out. Ranges are stored inclusive, whatever the [`Bounds`] of the input.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ParsedInput {
+    ranges: Vec<(usize, usize)>,
+    /// The line number of every range, counting from 1 like the warnings.
+    range_lines: Vec<usize>,
+    exclusions: Vec<(usize, usize)>,
+    numbers: Vec<usize>,
+    warnings: Vec<Warning>,
+}
+
+impl ParsedInput {
+    /// The IDs in any range but none of the exclusions, wherever the exclusions are listed.
+    fn fresh_ids(&self, merge_adjacent: bool) -> IntervalSet {
+        let mut fresh = IntervalSet::with_adjacent_merging(merge_adjacent);
+        fresh.extend(self.ranges.iter().copied());
+        let excluded: IntervalSet = self.exclusions.iter().copied().collect();
+        fresh.difference(&excluded)
+    }
+}
+
+/// Parse the ranges and `!a-b` exclusions, then after a blank line the IDs.
+fn parse_input(input: &str, bounds: Bounds) -> ParsedInput {
     let mut ranges = Vec::new();
+    let mut range_lines = Vec::new();
+    let mut exclusions = Vec::new();
     let mut numbers = Vec::new();
+    let mut warnings = Vec::new();
+    // The IDs start after the first blank line following a range, or at the first ID
+    let mut seen_range = false;
+    let mut in_ids = false;
 
-    for line in input.lines().map(|l| l.trim()) {
+    for (i, line) in input.lines().map(|l| l.trim()).enumerate() {
         if line.is_empty() {
+            in_ids |= seen_range;
             continue;
         }
 
-        if let Some((start, end)) = line.split_once('-') {
-            if let (Ok(start), Ok(end)) = (start.parse::<usize>(), end.parse::<usize>()) {
-                ranges.push((start, end));
+        let parsed = if line.contains('-') {
+            let (text, excluded) = match line.strip_prefix('!') {
+                Some(text) => (text, true),
+                None => (line, false),
+            };
+            parse_range(text, bounds).and_then(|range| {
+                if in_ids {
+                    return Err(Rejection::SectionOrder);
+                }
+                seen_range = true;
+                if excluded {
+                    exclusions.extend(range);
+                } else if let Some(range) = range {
+                    ranges.push(range);
+                    range_lines.push(i + 1);
+                }
+                Ok(())
+            })
+        } else {
+            parse_number(line).map(|number| {
+                in_ids = true;
+                numbers.push(number);
+            })
+        };
+        if let Err(rejection) = parsed {
+            warnings.push(Warning {
+                line_number: i + 1,
+                line: line.to_string(),
+                rejection,
+            });
+        }
+    }
+
+    ParsedInput {
+        ranges,
+        range_lines,
+        exclusions,
+        numbers,
+        warnings,
+    }
+}
+
+/// Parse `a-b` as an inclusive range, or as nothing for an empty half-open range like `4-4`.
+fn parse_range(text: &str, bounds: Bounds) -> Result<Option<(usize, usize)>, Rejection> {
+    let (start, end) = text.split_once('-').ok_or(Rejection::Garbage)?;
+    let (start, end) = (parse_number(start)?, parse_number(end)?);
+    if start > end {
+        return Err(Rejection::ReversedRange { start, end });
+    }
+    match bounds {
+        Bounds::Inclusive => Ok(Some((start, end))),
+        Bounds::HalfOpen if start == end => Ok(None),
+        Bounds::HalfOpen => Ok(Some((start, end - 1))),
+    }
+}
+
+fn parse_number(text: &str) -> Result<usize, Rejection> {
+    text.parse().map_err(|error: std::num::ParseIntError| {
+        if *error.kind() == std::num::IntErrorKind::PosOverflow {
+            Rejection::Overflow
+        } else {
+            Rejection::Garbage
+        }
+    })
+}
+
+/// A set of numbers stored as sorted, disjoint inclusive intervals. By default intervals that
+/// touch are merged, so every set has exactly one representation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct IntervalSet {
+    intervals: Vec<(usize, usize)>,
+    /// Whether intervals like `3-5` and `6-8` become `3-8`. This never changes which numbers are
+    /// in the set, only how it is split into intervals.
+    merge_adjacent: bool,
+}
+
+impl Default for IntervalSet {
+    fn default() -> Self {
+        IntervalSet::with_adjacent_merging(true)
+    }
+}
+
+impl IntervalSet {
+    fn new() -> Self {
+        Self::default()
+    }
+
+    fn with_adjacent_merging(merge_adjacent: bool) -> Self {
+        IntervalSet {
+            intervals: Vec::new(),
+            merge_adjacent,
+        }
+    }
+
+    /// How far apart two intervals can be and still get merged.
+    fn gap(&self) -> usize {
+        usize::from(self.merge_adjacent)
+    }
+
+    /// The merged intervals in ascending order.
+    fn intervals(&self) -> &[(usize, usize)] {
+        &self.intervals
+    }
+
+    /// Add all numbers from `start` to `end` inclusive. Nothing is added if `start > end`.
+    #[allow(dead_code)]
+    fn insert(&mut self, start: usize, end: usize) {
+        if start > end {
+            return;
+        }
+        // Intervals that overlap (or touch) `start..=end` are merged into it
+        let gap = self.gap();
+        let first = self
+            .intervals
+            .partition_point(|&(_, e)| e.saturating_add(gap) < start);
+        let last = self
+            .intervals
+            .partition_point(|&(s, _)| s <= end.saturating_add(gap));
+        let merged = if first < last {
+            (
+                start.min(self.intervals[first].0),
+                end.max(self.intervals[last - 1].1),
+            )
+        } else {
+            (start, end)
+        };
+        self.intervals.splice(first..last, [merged]);
+    }
+
+    /// Take away all numbers from `start` to `end` inclusive. Nothing is removed if `start > end`.
+    #[allow(dead_code)]
+    fn remove(&mut self, start: usize, end: usize) {
+        if start > end {
+            return;
+        }
+        let first = self.intervals.partition_point(|&(_, e)| e < start);
+        let last = self.intervals.partition_point(|&(s, _)| s <= end);
+        if first == last {
+            return;
+        }
+        // Only the first and last overlapping intervals can stick out on either side
+        let mut remainder = Vec::with_capacity(2);
+        let (first_start, _) = self.intervals[first];
+        let (_, last_end) = self.intervals[last - 1];
+        if first_start < start {
+            remainder.push((first_start, start - 1));
+        }
+        if last_end > end {
+            remainder.push((end + 1, last_end));
+        }
+        self.intervals.splice(first..last, remainder);
+    }
+
+    /// Whether `number` is in the set, by binary search.
+    fn contains(&self, number: usize) -> bool {
+        let i = self.intervals.partition_point(|&(_, end)| end < number);
+        self.intervals
+            .get(i)
+            .is_some_and(|&(start, _)| start <= number)
+    }
+
+    /// Like the other operations on two sets, this keeps the merging of `self`.
+    #[allow(dead_code)]
+    fn union(&self, other: &IntervalSet) -> IntervalSet {
+        let mut union = self.clone();
+        union.extend(other.intervals.iter().copied());
+        union
+    }
+
+    fn with_intervals(&self, intervals: Vec<(usize, usize)>) -> IntervalSet {
+        let mut set = IntervalSet::with_adjacent_merging(self.merge_adjacent);
+        set.extend(intervals);
+        set
+    }
+
+    #[allow(dead_code)]
+    fn intersection(&self, other: &IntervalSet) -> IntervalSet {
+        let mut intervals = Vec::new();
+        let (mut i, mut j) = (0, 0);
+        while let (Some(&(a_start, a_end)), Some(&(b_start, b_end))) =
+            (self.intervals.get(i), other.intervals.get(j))
+        {
+            let (start, end) = (a_start.max(b_start), a_end.min(b_end));
+            if start <= end {
+                intervals.push((start, end));
+            }
+            // Whichever interval ends first cannot overlap anything further on
+            if a_end < b_end {
+                i += 1;
             } else {
-                println!("{}-{}", start, end);
+                j += 1;
+            }
+        }
+        self.with_intervals(intervals)
+    }
+
+    fn difference(&self, other: &IntervalSet) -> IntervalSet {
+        let mut intervals = Vec::new();
+        let mut j = 0;
+        for &(start, end) in &self.intervals {
+            // Skip the intervals of `other` that end before this one starts
+            while other.intervals.get(j).is_some_and(|&(_, e)| e < start) {
+                j += 1;
+            }
+            // The start of the part not yet covered by `other`, if any is left
+            let mut start = Some(start);
+            let mut k = j;
+            while let (Some(s), Some(&(other_start, other_end))) = (start, other.intervals.get(k)) {
+                if other_start > end {
+                    break;
+                }
+                if s < other_start {
+                    intervals.push((s, other_start - 1));
+                }
+                start = other_end.checked_add(1).filter(|&next| next <= end);
+                k += 1;
+            }
+            if let Some(s) = start {
+                intervals.push((s, end));
             }
-        } else if let Ok(number) = line.parse::<usize>() {
-            numbers.push(number);
         }
+        self.with_intervals(intervals)
     }
 
-    (ranges, numbers)
+    /// The number of numbers in the set, which is one more than `usize::MAX` for the whole range.
+    fn len(&self) -> u128 {
+        self.intervals
+            .iter()
+            .map(|&(start, end)| (end - start) as u128 + 1)
+            .sum()
+    }
+
+    #[allow(dead_code)]
+    fn is_empty(&self) -> bool {
+        self.intervals.is_empty()
+    }
 }
 
-fn part_1(ranges: &[(usize, usize)], numbers: &[usize]) -> usize {
-    let mut count = 0;
-    for number in numbers {
+impl Extend<(usize, usize)> for IntervalSet {
+    /// Sort the new intervals together with the existing ones and merge them in one pass.
+    fn extend<I: IntoIterator<Item = (usize, usize)>>(&mut self, iter: I) {
+        let mut ranges = std::mem::take(&mut self.intervals);
+        ranges.extend(iter.into_iter().filter(|(start, end)| start <= end));
+        ranges.sort_unstable();
+
+        let gap = self.gap();
         for (start, end) in ranges {
-            if start <= number && number <= end {
-                count += 1;
-                break;
+            match self.intervals.last_mut() {
+                Some((_, current_end)) if start <= current_end.saturating_add(gap) => {
+                    *current_end = (*current_end).max(end);
+                }
+                _ => self.intervals.push((start, end)),
             }
         }
     }
-    count
 }
 
-fn part_2(ranges: &[(usize, usize)]) -> usize {
-    // Order Ranges by start, and then by end
-    let mut ranges = ranges.to_vec();
-    ranges.sort();
+impl FromIterator<(usize, usize)> for IntervalSet {
+    fn from_iter<I: IntoIterator<Item = (usize, usize)>>(iter: I) -> Self {
+        let mut set = IntervalSet::new();
+        set.extend(iter);
+        set
+    }
+}
 
-    // If two ranges overlap, merge them
-    let mut merged_ranges = Vec::new();
-    let (mut current_start, mut current_end) = ranges[0];
-    for (start, end) in &ranges[1..] {
-        if *start <= current_end {
-            current_end = current_end.max(*end);
-        } else {
-            merged_ranges.push((current_start, current_end));
-            current_start = *start;
-            current_end = *end;
+/// The original ranges in a static interval tree, to find every range containing a number rather
+/// than just whether there is one.
+///
+/// The ranges are sorted by start, and the tree over any slice of them has its root in the middle
+/// of the slice, so the tree needs no pointers and is balanced.
+#[derive(Debug, Clone)]
+struct IntervalTree {
+    /// `(start, end, index)` of every range, with `index` its position in the input.
+    nodes: Vec<(usize, usize, usize)>,
+    /// The largest end in the subtree rooted at every node.
+    max_ends: Vec<usize>,
+}
+
+impl IntervalTree {
+    /// Build the tree, leaving out reversed ranges.
+    fn new(ranges: &[(usize, usize)]) -> Self {
+        let mut nodes: Vec<(usize, usize, usize)> = ranges
+            .iter()
+            .enumerate()
+            .filter(|(_, (start, end))| start <= end)
+            .map(|(index, &(start, end))| (start, end, index))
+            .collect();
+        nodes.sort_unstable();
+        let mut tree = IntervalTree {
+            max_ends: vec![0; nodes.len()],
+            nodes,
+        };
+        tree.compute_max_ends(0, tree.nodes.len());
+        tree
+    }
+
+    /// Fill in `max_ends` for the subtree over `nodes[low..high]`, and return its largest end.
+    fn compute_max_ends(&mut self, low: usize, high: usize) -> Option<usize> {
+        if low >= high {
+            return None;
         }
+        let middle = low + (high - low) / 2;
+        let left = self.compute_max_ends(low, middle);
+        let right = self.compute_max_ends(middle + 1, high);
+        let max_end = [left, right, Some(self.nodes[middle].1)]
+            .into_iter()
+            .flatten()
+            .max()
+            .unwrap();
+        self.max_ends[middle] = max_end;
+        Some(max_end)
     }
-    merged_ranges.push((current_start, current_end));
 
-    merged_ranges
+    /// The input positions of all ranges containing `number`, in ascending order.
+    fn containing(&self, number: usize) -> Vec<usize> {
+        let mut indices = Vec::new();
+        self.collect_containing(number, 0, self.nodes.len(), &mut indices);
+        indices.sort_unstable();
+        indices
+    }
+
+    fn collect_containing(&self, number: usize, low: usize, high: usize, indices: &mut Vec<usize>) {
+        if low >= high {
+            return;
+        }
+        let middle = low + (high - low) / 2;
+        // Nothing in this subtree reaches `number`
+        if self.max_ends[middle] < number {
+            return;
+        }
+        self.collect_containing(number, low, middle, indices);
+        let (start, end, index) = self.nodes[middle];
+        // Ranges further right start even later
+        if start > number {
+            return;
+        }
+        if number <= end {
+            indices.push(index);
+        }
+        self.collect_containing(number, middle + 1, high, indices);
+    }
+}
+
+/// A stretch of numbers that are all in the same number of ranges.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Coverage {
+    start: usize,
+    end: usize,
+    multiplicity: usize,
+}
+
+/// How many ranges every number is in, as maximal stretches of equal multiplicity in ascending
+/// order. Numbers outside all ranges are left out.
+fn coverage(ranges: &[(usize, usize)]) -> Vec<Coverage> {
+    // A range adds one from its start and takes it away again after its end, which is never
+    // reached for ranges up to `usize::MAX`
+    let mut events: Vec<(usize, isize)> = Vec::with_capacity(2 * ranges.len());
+    for &(start, end) in ranges.iter().filter(|(start, end)| start <= end) {
+        events.push((start, 1));
+        if let Some(after) = end.checked_add(1) {
+            events.push((after, -1));
+        }
+    }
+    events.sort_unstable();
+
+    let mut segments = Vec::new();
+    let (mut multiplicity, mut segment_start): (usize, usize) = (0, 0);
+    let mut i = 0;
+    while i < events.len() {
+        let position = events[i].0;
+        let mut new_multiplicity = multiplicity;
+        while i < events.len() && events[i].0 == position {
+            new_multiplicity = new_multiplicity.checked_add_signed(events[i].1).unwrap();
+            i += 1;
+        }
+        if new_multiplicity == multiplicity {
+            continue;
+        }
+        if multiplicity > 0 {
+            segments.push(Coverage {
+                start: segment_start,
+                end: position - 1,
+                multiplicity,
+            });
+        }
+        (multiplicity, segment_start) = (new_multiplicity, position);
+    }
+    if multiplicity > 0 {
+        segments.push(Coverage {
+            start: segment_start,
+            end: usize::MAX,
+            multiplicity,
+        });
+    }
+    segments
+}
+
+/// The smallest number in the most ranges, and how many ranges that is.
+fn maximum_overlap(segments: &[Coverage]) -> Option<(usize, usize)> {
+    segments
         .iter()
-        .map(|(start, end)| end - start + 1)
-        .sum()
+        .rev()
+        .max_by_key(|segment| segment.multiplicity)
+        .map(|segment| (segment.start, segment.multiplicity))
+}
+
+fn part_1(fresh: &IntervalSet, numbers: &[usize]) -> usize {
+    numbers
+        .iter()
+        .filter(|&&number| fresh.contains(number))
+        .count()
+}
+
+fn part_2(fresh: &IntervalSet) -> u128 {
+    fresh.len()
 }
 
 #[cfg(test)]
@@ -88,35 +737,277 @@ mod tests {
     #[test]
     fn test_parse_input() {
         assert_eq!(
-            parse_input(TEST_INPUT),
-            (
-                vec![(3, 5), (10, 14), (16, 20), (12, 18)],
-                vec![1, 5, 8, 11, 17, 32]
-            )
+            parse_input(TEST_INPUT, Bounds::Inclusive),
+            ParsedInput {
+                ranges: vec![(3, 5), (10, 14), (16, 20), (12, 18)],
+                range_lines: vec![2, 3, 4, 5],
+                exclusions: vec![],
+                numbers: vec![1, 5, 8, 11, 17, 32],
+                warnings: vec![],
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_warnings() {
+        let input = "
+            3-5
+            9-7
+            10-99999999999999999999
+            ten-12
+
+            1
+            14-16
+            x
+            -4
+            ";
+        let parsed = parse_input(input, Bounds::Inclusive);
+        assert_eq!(parsed.ranges, vec![(3, 5)]);
+        assert_eq!(parsed.range_lines, vec![2]);
+        assert_eq!(parsed.numbers, vec![1]);
+        let rejections: Vec<(usize, Rejection)> = parsed
+            .warnings
+            .iter()
+            .map(|warning| (warning.line_number, warning.rejection.clone()))
+            .collect();
+        assert_eq!(
+            rejections,
+            vec![
+                (3, Rejection::ReversedRange { start: 9, end: 7 }),
+                (4, Rejection::Overflow),
+                (5, Rejection::Garbage),
+                (8, Rejection::SectionOrder),
+                (9, Rejection::Garbage),
+                (10, Rejection::Garbage),
+            ]
+        );
+        assert_eq!(
+            parsed.warnings[3].to_string(),
+            "line 8: range \"14-16\" after the IDs"
+        );
+
+        // Without a blank line the IDs start at the first ID
+        let parsed = parse_input("1-2\n3\n4-5", Bounds::Inclusive);
+        assert_eq!(parsed.warnings[0].rejection, Rejection::SectionOrder);
+    }
+
+    /// Every number up to `limit` in the set.
+    fn members(set: &IntervalSet, limit: usize) -> Vec<usize> {
+        (0..=limit).filter(|&number| set.contains(number)).collect()
+    }
+
+    #[test]
+    fn test_interval_set_insert_remove() {
+        let mut set = IntervalSet::new();
+        set.insert(10, 14);
+        set.insert(3, 5);
+        set.insert(16, 20);
+        assert_eq!(set.intervals(), [(3, 5), (10, 14), (16, 20)]);
+        // Touching intervals are merged as well as overlapping ones
+        set.insert(15, 15);
+        assert_eq!(set.intervals(), [(3, 5), (10, 20)]);
+        set.insert(6, 8);
+        assert_eq!(set.intervals(), [(3, 8), (10, 20)]);
+        set.insert(1, 30);
+        assert_eq!(set.intervals(), [(1, 30)]);
+        set.insert(7, 3);
+        assert_eq!(set.len(), 30);
+
+        set.remove(10, 12);
+        set.remove(20, 40);
+        set.remove(0, 1);
+        assert_eq!(set.intervals(), [(2, 9), (13, 19)]);
+        set.remove(5, 15);
+        assert_eq!(set.intervals(), [(2, 4), (16, 19)]);
+        assert!(set.contains(2) && set.contains(19));
+        assert!(!set.contains(1) && !set.contains(5) && !set.contains(20));
+        set.remove(0, usize::MAX);
+        assert!(set.is_empty());
+
+        let mut set = IntervalSet::new();
+        set.insert(usize::MAX - 1, usize::MAX);
+        set.insert(0, 0);
+        set.remove(usize::MAX, usize::MAX);
+        assert_eq!(set.intervals(), [(0, 0), (usize::MAX - 1, usize::MAX - 1)]);
+
+        let whole: IntervalSet = [(0, usize::MAX)].into_iter().collect();
+        assert_eq!(whole.len(), usize::MAX as u128 + 1);
+        let fresh = parse_input("0-18446744073709551615\n", Bounds::Inclusive).fresh_ids(true);
+        assert_eq!(part_2(&fresh), 1 << 64);
+    }
+
+    #[test]
+    fn test_interval_set_operations() {
+        let a: IntervalSet = [(3, 5), (10, 14), (16, 20), (12, 18)].into_iter().collect();
+        let b: IntervalSet = [(1, 3), (6, 6), (12, 12), (15, 17), (20, 25)]
+            .into_iter()
+            .collect();
+        assert_eq!(a.intervals(), [(3, 5), (10, 20)]);
+        assert_eq!(
+            b.intervals(),
+            [(1, 3), (6, 6), (12, 12), (15, 17), (20, 25)]
+        );
+
+        let a_members = members(&a, 30);
+        let b_members = members(&b, 30);
+        let union = members(&a.union(&b), 30);
+        let intersection = members(&a.intersection(&b), 30);
+        let difference = members(&a.difference(&b), 30);
+        for number in 0..=30 {
+            let (in_a, in_b) = (a_members.contains(&number), b_members.contains(&number));
+            assert_eq!(union.contains(&number), in_a || in_b);
+            assert_eq!(intersection.contains(&number), in_a && in_b);
+            assert_eq!(difference.contains(&number), in_a && !in_b);
+        }
+        assert_eq!(
+            a.difference(&b).intervals(),
+            [(4, 5), (10, 11), (13, 14), (18, 19)]
+        );
+        assert_eq!(a.intersection(&b).len(), 6);
+        assert_eq!(a.union(&b).len(), 22);
+        assert!(a.difference(&a).is_empty());
+    }
+
+    #[test]
+    fn test_answer_queries() {
+        let ranges = parse_input(TEST_INPUT, Bounds::Inclusive).ranges;
+        let fresh: IntervalSet = ranges.into_iter().collect();
+        let queries = "1\n5\n\n8\nabc\n11\n17\n32\n";
+
+        let mut output = Vec::new();
+        let counts = answer_queries(&fresh, queries.as_bytes(), &mut output, None, 1).unwrap();
+        assert_eq!(counts, (3, 3));
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "1 spoiled\n5 fresh\n8 spoiled\n11 fresh\n17 fresh\n32 spoiled\n"
+        );
+
+        let mut output = Vec::new();
+        let filter = Some(Freshness::Fresh);
+        answer_queries(&fresh, queries.as_bytes(), &mut output, filter, 4).unwrap();
+        assert_eq!(String::from_utf8(output).unwrap(), "5\n11\n17\n");
+        assert_eq!("spoiled".parse(), Ok(Freshness::Spoiled));
+    }
+
+    #[test]
+    fn test_interval_tree() {
+        let ranges = parse_input(TEST_INPUT, Bounds::Inclusive).ranges;
+        let tree = IntervalTree::new(&ranges);
+        assert_eq!(tree.containing(1), vec![]);
+        assert_eq!(tree.containing(5), vec![0]);
+        assert_eq!(tree.containing(12), vec![1, 3]);
+        assert_eq!(tree.containing(16), vec![2, 3]);
+        assert_eq!(tree.containing(21), vec![]);
+
+        // Compare with checking every range on many overlapping ranges
+        let ranges: Vec<(usize, usize)> = (0..200)
+            .map(|i| ((i * 37) % 101, (i * 37) % 101 + (i * 13) % 17))
+            .chain([(50, 10), (0, usize::MAX)])
+            .collect();
+        let tree = IntervalTree::new(&ranges);
+        for number in 0..130 {
+            let expected: Vec<usize> = (0..ranges.len())
+                .filter(|&i| ranges[i].0 <= number && number <= ranges[i].1)
+                .collect();
+            assert_eq!(tree.containing(number), expected);
+        }
+    }
+
+    #[test]
+    fn test_coverage() {
+        let ranges = parse_input(TEST_INPUT, Bounds::Inclusive).ranges;
+        let segments = coverage(&ranges);
+        let stretches: Vec<(usize, usize, usize)> = segments
+            .iter()
+            .map(|segment| (segment.start, segment.end, segment.multiplicity))
+            .collect();
+        assert_eq!(
+            stretches,
+            vec![
+                (3, 5, 1),
+                (10, 11, 1),
+                (12, 14, 2),
+                (15, 15, 1),
+                (16, 18, 2),
+                (19, 20, 1)
+            ]
+        );
+        assert_eq!(maximum_overlap(&segments), Some((12, 2)));
+
+        let segments = coverage(&[(5, usize::MAX), (1, 6), (7, 3), (6, 6)]);
+        assert_eq!(
+            segments.last(),
+            Some(&Coverage {
+                start: 7,
+                end: usize::MAX,
+                multiplicity: 1
+            })
+        );
+        assert_eq!(maximum_overlap(&segments), Some((6, 3)));
+        assert_eq!(maximum_overlap(&coverage(&[])), None);
+    }
+
+    #[test]
+    fn test_range_semantics() {
+        let input = "
+            3-5
+            6-8
+            !4-4
+            10-20
+            !15-30
+
+            4
+            6
+            9
+            ";
+        let parsed = parse_input(input, Bounds::Inclusive);
+        assert_eq!(parsed.exclusions, vec![(4, 4), (15, 30)]);
+        let fresh = parsed.fresh_ids(true);
+        assert_eq!(fresh.intervals(), [(3, 3), (5, 8), (10, 14)]);
+        assert_eq!(part_1(&fresh, &parsed.numbers), 1);
+        assert_eq!(part_2(&fresh), 10);
+        assert_eq!(
+            parsed.fresh_ids(false).intervals(),
+            [(3, 3), (5, 5), (6, 8), (10, 14)]
+        );
+
+        let parsed = parse_input(input, Bounds::HalfOpen);
+        assert_eq!(parsed.ranges, vec![(3, 4), (6, 7), (10, 19)]);
+        assert_eq!(parsed.range_lines, vec![2, 3, 5]);
+        // `!4-4` is empty, so it is left out without a warning
+        assert_eq!(parsed.exclusions, vec![(15, 29)]);
+        assert!(parsed.warnings.is_empty());
+        let fresh = parsed.fresh_ids(true);
+        assert_eq!(fresh.intervals(), [(3, 4), (6, 7), (10, 14)]);
+        assert_eq!(part_2(&fresh), 9);
+        assert_eq!(Bounds::HalfOpen.format((10, 14)), "10-15");
+        assert_eq!(
+            Bounds::HalfOpen.format((0, usize::MAX)),
+            "0-18446744073709551616"
         );
     }
 
     #[test]
     fn test_part_1_simple() {
-        let (ranges, numbers) = parse_input(TEST_INPUT);
-        assert_eq!(part_1(&ranges, &numbers), 3);
+        let parsed = parse_input(TEST_INPUT, Bounds::Inclusive);
+        assert_eq!(part_1(&parsed.fresh_ids(true), &parsed.numbers), 3);
     }
 
     #[test]
     fn test_part_1_final() {
-        let (ranges, numbers) = parse_input(INPUT);
-        assert_eq!(part_1(&ranges, &numbers), 617);
+        let parsed = parse_input(INPUT, Bounds::Inclusive);
+        assert_eq!(part_1(&parsed.fresh_ids(true), &parsed.numbers), 617);
     }
 
     #[test]
     fn test_part_2_simple() {
-        let (ranges, _) = parse_input(TEST_INPUT);
-        assert_eq!(part_2(&ranges), 14);
+        let parsed = parse_input(TEST_INPUT, Bounds::Inclusive);
+        assert_eq!(part_2(&parsed.fresh_ids(true)), 14);
     }
 
     #[test]
     fn test_part_2_final() {
-        let (ranges, _) = parse_input(INPUT);
-        assert_eq!(part_2(&ranges), 338258295736104);
+        let parsed = parse_input(INPUT, Bounds::Inclusive);
+        assert_eq!(part_2(&parsed.fresh_ids(true)), 338258295736104);
     }
 }
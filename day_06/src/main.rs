@@ -1,140 +1,545 @@
+use std::ops::Range;
+
 const INPUT: &str = include_str!("../input.txt");
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum Operator {
+    /// `+`
     Addition,
+    /// `*`
     Multiplication,
+    /// `-`
+    Subtraction,
+    /// `/`, rounding towards zero.
+    Division,
+    /// `<`, the smaller operand.
+    Minimum,
+    /// `>`, the larger operand.
+    Maximum,
+    /// `|`, the digits of the second operand written after the first, so `-12 | 3` is `-123`.
+    /// The second operand must not be negative.
+    Concatenation,
+    /// `^`, the first operand raised to the second, which must not be negative.
+    Power,
+}
+
+/// Why a problem has no answer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum EvalError {
+    DivisionByZero,
+    /// The result does not fit in an `isize`.
+    Overflow(Operator),
+    /// A negative second operand where only non-negative ones make sense.
+    NegativeOperand(Operator),
+    /// A problem without numbers.
+    NoOperands,
+}
+
+impl std::fmt::Display for EvalError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            EvalError::DivisionByZero => write!(f, "division by zero"),
+            EvalError::Overflow(operator) => write!(f, "overflow in {:?}", operator),
+            EvalError::NegativeOperand(operator) => {
+                write!(f, "negative second operand in {:?}", operator)
+            }
+            EvalError::NoOperands => write!(f, "problem without numbers"),
+        }
+    }
 }
 
 impl Operator {
-    fn apply(&self, a: isize, b: isize) -> isize {
+    fn from_symbol(symbol: char) -> Option<Operator> {
+        match symbol {
+            '+' => Some(Operator::Addition),
+            '*' => Some(Operator::Multiplication),
+            '-' => Some(Operator::Subtraction),
+            '/' => Some(Operator::Division),
+            '<' => Some(Operator::Minimum),
+            '>' => Some(Operator::Maximum),
+            '|' => Some(Operator::Concatenation),
+            '^' => Some(Operator::Power),
+            _ => None,
+        }
+    }
+
+    fn apply(&self, a: isize, b: isize) -> Result<isize, EvalError> {
+        let overflow = EvalError::Overflow(*self);
         match self {
-            Operator::Addition => a + b,
-            Operator::Multiplication => a * b,
+            Operator::Addition => a.checked_add(b).ok_or(overflow),
+            Operator::Multiplication => a.checked_mul(b).ok_or(overflow),
+            Operator::Subtraction => a.checked_sub(b).ok_or(overflow),
+            Operator::Division if b == 0 => Err(EvalError::DivisionByZero),
+            Operator::Division => a.checked_div(b).ok_or(overflow),
+            Operator::Minimum => Ok(a.min(b)),
+            Operator::Maximum => Ok(a.max(b)),
+            Operator::Concatenation => {
+                let b = usize::try_from(b).map_err(|_| EvalError::NegativeOperand(*self))?;
+                let shift = 10isize.checked_pow(b.checked_ilog10().unwrap_or(0) + 1);
+                let magnitude = shift
+                    .and_then(|shift| a.unsigned_abs().checked_mul(shift.unsigned_abs()))
+                    .and_then(|shifted| shifted.checked_add(b))
+                    .ok_or(overflow)?;
+                if a < 0 {
+                    0isize.checked_sub_unsigned(magnitude).ok_or(overflow)
+                } else {
+                    isize::try_from(magnitude).map_err(|_| overflow)
+                }
+            }
+            Operator::Power => match u32::try_from(b) {
+                Ok(exponent) => a.checked_pow(exponent).ok_or(overflow),
+                Err(_) if b < 0 => Err(EvalError::NegativeOperand(*self)),
+                // Only 0, 1 and -1 stay in range for exponents this large
+                Err(_) => match a {
+                    0 | 1 => Ok(a),
+                    -1 if b % 2 == 0 => Ok(1),
+                    -1 => Ok(-1),
+                    _ => Err(overflow),
+                },
+            },
         }
     }
 }
 
 fn main() {
     let input = parse_input_part_1(INPUT);
-    println!("Part 1: {}", apply_and_sum(input));
+    match apply_and_sum(input) {
+        Ok(sum) => println!("Part 1: {}", sum),
+        Err(error) => println!("Part 1: {}", error),
+    }
     let input = parse_input_part_2(INPUT);
-    println!("Part 2: {}", apply_and_sum(input));
+    match apply_and_sum(input) {
+        Ok(sum) => println!("Part 2: {}", sum),
+        Err(error) => println!("Part 2: {}", error),
+    }
+
+    // Options: `--input <path>` and `--mode <rows|top-down|bottom-up|right-to-left>` to evaluate
+    // another worksheet or read it another way, and `--render <mode>` to print its problems as a
+    // worksheet to be read in that mode instead. `--report` lists every problem with its answer
+    let mut args = std::env::args().skip(1).peekable();
+    if args.peek().is_none() {
+        return;
+    }
+    let mut input = INPUT.to_string();
+    let mut mode = ReadingMode::Rows;
+    let mut render_mode = None;
+    let mut show_report = false;
+    while let Some(option) = args.next() {
+        if option == "--report" {
+            show_report = true;
+            continue;
+        }
+        let value = args.next().expect("Missing option value");
+        match option.as_str() {
+            "--input" => input = std::fs::read_to_string(value).expect("Could not read input"),
+            "--mode" => mode = value.parse().unwrap_or_else(|error| panic!("{}", error)),
+            "--render" => {
+                render_mode = Some(value.parse().unwrap_or_else(|error| panic!("{}", error)))
+            }
+            _ => panic!("Unknown option: {}", option),
+        }
+    }
+    if show_report {
+        for line in report(&parse_problems(&input, mode), mode) {
+            println!("{}", line);
+        }
+    }
+    if let Some(render_mode) = render_mode {
+        let problems = parse_worksheet(&input, mode);
+        match render_worksheet(&problems, render_mode) {
+            Ok(worksheet) => print!("{}", worksheet),
+            Err(error) => println!("{}", error),
+        }
+        return;
+    }
+    match apply_and_sum(parse_worksheet(&input, mode)) {
+        Ok(sum) => println!("{:?}: {}", mode, sum),
+        Err(error) => println!("{:?}: {}", mode, error),
+    }
 }
 
-fn parse_input_part_1(input: &str) -> Vec<(Vec<isize>, Operator)> {
-    let lines: Vec<&str> = input
-        .lines()
-        .filter(|line| !line.trim().is_empty())
-        .collect();
-
-    // Split the lines - last line contains operators, rest contain numbers
-    let (number_lines, operator_line) = lines.split_at(lines.len() - 1);
-    let operator_line = operator_line[0];
-
-    // Parse operators
-    let operators: Vec<Operator> = operator_line
-        .split_whitespace()
-        .map(|op| match op {
-            "*" => Operator::Multiplication,
-            "+" => Operator::Addition,
-            _ => panic!("Unknown operator: {}", op),
-        })
-        .collect();
+/// How the numbers of a problem are read from its block of the worksheet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ReadingMode {
+    /// Every line is a number, from top to bottom.
+    Rows,
+    /// Every column is a number with its most significant digit on top, from left to right.
+    ColumnsTopDown,
+    /// Every column is a number with its most significant digit at the bottom, from left to right.
+    ColumnsBottomUp,
+    /// Every column is a number with its most significant digit on top, from right to left.
+    RightToLeft,
+}
+
+impl std::str::FromStr for ReadingMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "rows" => Ok(ReadingMode::Rows),
+            "top-down" => Ok(ReadingMode::ColumnsTopDown),
+            "bottom-up" => Ok(ReadingMode::ColumnsBottomUp),
+            "right-to-left" => Ok(ReadingMode::RightToLeft),
+            _ => Err(format!("Unknown reading mode: {}", s)),
+        }
+    }
+}
+
+impl ReadingMode {
+    /// The numbers of the problem in `columns` of the number lines of `worksheet`.
+    fn read(&self, worksheet: &Worksheet, columns: Range<usize>) -> Vec<isize> {
+        let mut rows: Vec<usize> = (0..worksheet.height - 1).collect();
+        if *self == ReadingMode::Rows {
+            return rows
+                .into_iter()
+                .map(|y| worksheet.row(y, columns.clone()).trim_ascii())
+                .filter(|number| !number.is_empty())
+                .map(parse_number)
+                .collect();
+        }
 
-    // Parse numbers by columns
-    let mut columns: Vec<Vec<isize>> = vec![Vec::new(); operators.len()];
+        if *self == ReadingMode::ColumnsBottomUp {
+            rows.reverse();
+        }
+        let mut columns: Vec<usize> = columns.collect();
+        if *self == ReadingMode::RightToLeft {
+            columns.reverse();
+        }
+        columns
+            .into_iter()
+            .filter_map(|x| {
+                let digits: Vec<u8> = rows
+                    .iter()
+                    .map(|&y| worksheet.get(x, y))
+                    .filter(|c| c.is_ascii_digit())
+                    .collect();
+                // Columns without digits separate nothing within a problem, so skip them
+                (!digits.is_empty()).then(|| parse_number(&digits))
+            })
+            .collect()
+    }
+}
+
+fn parse_number(text: &[u8]) -> isize {
+    std::str::from_utf8(text)
+        .ok()
+        .and_then(|text| text.parse().ok())
+        .unwrap_or_else(|| panic!("Invalid number: {}", String::from_utf8_lossy(text)))
+}
 
-    for line in number_lines {
-        let numbers: Vec<isize> = line
-            .split_whitespace()
-            .map(|n| n.parse().unwrap())
+/// A worksheet as a grid of bytes, with every line padded with spaces to the same width so that
+/// any cell can be reached by index.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Worksheet {
+    width: usize,
+    height: usize,
+    cells: Vec<u8>,
+}
+
+impl Worksheet {
+    /// Blank lines are left out.
+    fn new(input: &str) -> Self {
+        let lines: Vec<&[u8]> = input
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| line.as_bytes())
             .collect();
+        let width = lines.iter().map(|line| line.len()).max().unwrap_or(0);
+        let mut cells = Vec::with_capacity(width * lines.len());
+        for line in &lines {
+            cells.extend_from_slice(line);
+            cells.resize(cells.len() + width - line.len(), b' ');
+        }
+        Worksheet {
+            width,
+            height: lines.len(),
+            cells,
+        }
+    }
 
-        for (col_idx, &num) in numbers.iter().enumerate() {
-            columns[col_idx].push(num);
+    fn get(&self, x: usize, y: usize) -> u8 {
+        self.cells[x + y * self.width]
+    }
+
+    fn row(&self, y: usize, columns: Range<usize>) -> &[u8] {
+        &self.cells[y * self.width..][columns]
+    }
+
+    /// The columns of every problem, which are separated by columns that are blank on every line.
+    fn problem_columns(&self) -> Vec<Range<usize>> {
+        let mut blank = vec![true; self.width];
+        for row in self.cells.chunks(self.width) {
+            for (x, &c) in row.iter().enumerate() {
+                blank[x] &= c.is_ascii_whitespace();
+            }
         }
+
+        let mut problems = Vec::new();
+        let mut start = None;
+        for (x, &blank) in blank.iter().chain([&true]).enumerate() {
+            match (start, blank) {
+                (None, false) => start = Some(x),
+                (Some(first), true) => {
+                    problems.push(first..x);
+                    start = None;
+                }
+                _ => {}
+            }
+        }
+        problems
     }
+}
 
-    // Combine columns with operators
-    columns.into_iter().zip(operators).collect()
+/// Parse the problems of a worksheet: lines of numbers with a line of operators at the bottom,
+/// and a blank column between problems. `mode` says how the numbers of a problem are read from
+/// its columns.
+fn parse_worksheet(input: &str, mode: ReadingMode) -> Vec<(Vec<isize>, Operator)> {
+    parse_problems(input, mode)
+        .into_iter()
+        .map(|problem| (problem.numbers, problem.operator))
+        .collect()
+}
+
+/// A problem with the columns of the worksheet it was read from, counting from 0.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Problem {
+    columns: Range<usize>,
+    numbers: Vec<isize>,
+    operator: Operator,
 }
 
-fn apply_and_sum(input: Vec<(Vec<isize>, Operator)>) -> isize {
-    input
+/// Like [`parse_worksheet`], but keeping where every problem was found.
+fn parse_problems(input: &str, mode: ReadingMode) -> Vec<Problem> {
+    let worksheet = Worksheet::new(input);
+    let operator_row = worksheet.height - 1;
+
+    worksheet
+        .problem_columns()
         .into_iter()
-        .map(|(nums, op)| {
-            nums.into_iter()
-                .reduce(|acc, num| op.apply(acc, num))
-                .unwrap()
+        .map(|columns| {
+            let symbols: Vec<u8> = worksheet
+                .row(operator_row, columns.clone())
+                .iter()
+                .copied()
+                .filter(|c| !c.is_ascii_whitespace())
+                .collect();
+            let operator = match symbols[..] {
+                [symbol] => Operator::from_symbol(char::from(symbol))
+                    .unwrap_or_else(|| panic!("Unknown operator: {}", char::from(symbol))),
+                _ => panic!("Expected one operator in columns {:?}", columns),
+            };
+            Problem {
+                numbers: mode.read(&worksheet, columns.clone()),
+                columns,
+                operator,
+            }
         })
-        .sum()
+        .collect()
 }
 
-fn parse_input_part_2(input: &str) -> Vec<(Vec<isize>, Operator)> {
-    let lines: Vec<&str> = input
-        .lines()
-        .filter(|line| !line.trim().is_empty())
-        .collect();
-    let max_line_length = lines.iter().map(|line| line.len()).max().unwrap();
-
-    // Split the lines - last line contains operators, rest contain numbers
-    let (number_lines, operator_line) = lines.split_at(lines.len() - 1);
-    let operator_line = operator_line[0];
-
-    // Read Operators first
-    // Each problem starts with an operator
-    // The gap to the next operator - 1 tells us how wide each column is
-    let mut operators: Vec<Operator> = Vec::new();
-    let mut problem_widths: Vec<usize> = Vec::new();
-
-    for (i, c) in operator_line.chars().enumerate() {
-        let operator = match c {
-            '+' => Some(Operator::Addition),
-            '*' => Some(Operator::Multiplication),
-            _ => None,
+impl Operator {
+    fn symbol(&self) -> char {
+        match self {
+            Operator::Addition => '+',
+            Operator::Multiplication => '*',
+            Operator::Subtraction => '-',
+            Operator::Division => '/',
+            Operator::Minimum => '<',
+            Operator::Maximum => '>',
+            Operator::Concatenation => '|',
+            Operator::Power => '^',
+        }
+    }
+}
+
+/// Write problems as a worksheet that [`parse_worksheet`] reads back in `mode`. Numbers are
+/// right-aligned in the row layout. In the column layouts every number is a column of digits,
+/// starting on the line it is read from. Every operator is in the first column of its problem.
+///
+/// Negative numbers cannot be written in the column layouts, where only digits are read.
+fn render_worksheet(
+    problems: &[(Vec<isize>, Operator)],
+    mode: ReadingMode,
+) -> Result<String, String> {
+    // Every problem as a block of lines of the same width, the last one for the operator
+    let mut blocks: Vec<Vec<Vec<u8>>> = Vec::new();
+    for (numbers, operator) in problems {
+        let mut block: Vec<Vec<u8>> = if mode == ReadingMode::Rows {
+            let texts: Vec<String> = numbers.iter().map(|number| number.to_string()).collect();
+            let width = texts.iter().map(|text| text.len()).max().unwrap_or(1);
+            texts
+                .iter()
+                .map(|text| format!("{:>width$}", text).into_bytes())
+                .collect()
+        } else {
+            if let Some(number) = numbers.iter().find(|&&number| number < 0) {
+                return Err(format!("Cannot write {} in a column layout", number));
+            }
+            let mut columns: Vec<Vec<u8>> = numbers
+                .iter()
+                .map(|number| number.to_string().into_bytes())
+                .collect();
+            if mode == ReadingMode::RightToLeft {
+                columns.reverse();
+            }
+            let height = columns.iter().map(|column| column.len()).max().unwrap_or(0);
+            (0..height)
+                .map(|y| {
+                    columns
+                        .iter()
+                        .map(|column| match mode {
+                            ReadingMode::ColumnsBottomUp if y + column.len() >= height => {
+                                column[height - 1 - y]
+                            }
+                            ReadingMode::ColumnsBottomUp => b' ',
+                            _ => column.get(y).copied().unwrap_or(b' '),
+                        })
+                        .collect()
+                })
+                .collect()
         };
+        let width = block.first().map_or(1, |line| line.len()).max(1);
+        let mut operator_line = vec![b' '; width];
+        operator_line[0] = operator.symbol() as u8;
+        block.push(operator_line);
+        blocks.push(block);
+    }
 
-        if operator.is_none() {
-            continue;
+    // Problems with fewer lines of numbers get blank lines at the bottom
+    let number_lines = blocks
+        .iter()
+        .map(|block| block.len() - 1)
+        .max()
+        .unwrap_or(0);
+    let mut lines = vec![Vec::new(); number_lines + 1];
+    for (i, block) in blocks.iter().enumerate() {
+        let width = block.last().unwrap().len();
+        let operator_line = block.len() - 1;
+        for (y, line) in lines.iter_mut().enumerate() {
+            if i > 0 {
+                line.push(b' ');
+            }
+            match y {
+                y if y == number_lines => line.extend_from_slice(&block[operator_line]),
+                y if y < operator_line => line.extend_from_slice(&block[y]),
+                _ => line.resize(line.len() + width, b' '),
+            }
         }
+    }
+    let mut worksheet = String::new();
+    for line in lines {
+        worksheet.push_str(String::from_utf8(line).unwrap().trim_end());
+        worksheet.push('\n');
+    }
+    Ok(worksheet)
+}
 
-        if i > 0 {
-            assert!(!operators.is_empty(),);
-            problem_widths.push(i - problem_widths.iter().sum::<usize>() - operators.len());
+fn parse_input_part_1(input: &str) -> Vec<(Vec<isize>, Operator)> {
+    parse_worksheet(input, ReadingMode::Rows)
+}
+
+fn parse_input_part_2(input: &str) -> Vec<(Vec<isize>, Operator)> {
+    parse_worksheet(input, ReadingMode::ColumnsTopDown)
+}
+
+/// Apply the operator of every problem to its numbers from left to right, and add up the answers.
+fn apply_and_sum(input: Vec<(Vec<isize>, Operator)>) -> Result<isize, EvalError> {
+    input.into_iter().try_fold(0isize, |sum, (nums, op)| {
+        sum.checked_add(evaluate(&nums, op)?)
+            .ok_or(EvalError::Overflow(Operator::Addition))
+    })
+}
+
+fn evaluate(numbers: &[isize], operator: Operator) -> Result<isize, EvalError> {
+    let (first, rest) = numbers.split_first().ok_or(EvalError::NoOperands)?;
+    rest.iter()
+        .try_fold(*first, |acc, &num| operator.apply(acc, num))
+}
+
+/// Answers that are correct but may come from misreading the worksheet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Suspicion {
+    /// A different number of operands than most problems have. Only when reading rows, where every
+    /// line holds one operand: in the columns the count is the width of the problem.
+    OperandCount { usual: usize },
+    /// A zero after the first operand of a product or power, which makes the answer 0 or 1
+    /// whatever the other operands are.
+    ZeroOperand,
+    /// An answer too large to be stored exactly as a double, as in a spreadsheet.
+    Imprecise,
+}
+
+impl std::fmt::Display for Suspicion {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Suspicion::OperandCount { usual } => write!(f, "usually {} operands", usual),
+            Suspicion::ZeroOperand => write!(f, "zero operand"),
+            Suspicion::Imprecise => write!(f, "beyond double precision"),
         }
-        operators.push(operator.unwrap());
     }
-    problem_widths
-        .push(max_line_length - problem_widths.iter().sum::<usize>() - operators.len() + 1);
-    assert_eq!(operators.len(), problem_widths.len());
+}
 
-    // Now we can parse the individual problems
-    let mut problems = Vec::new();
-    let mut current_problem = Vec::new();
-    let mut current_problem_column_start = 0;
-    let mut current_column = 0;
+/// Integers up to this magnitude can all be stored exactly as a double.
+const MAX_PRECISE: usize = 1 << 53;
 
-    for problem_width in problem_widths {
-        while current_column < current_problem_column_start + problem_width {
-            let mut number_str = String::new();
-            for number_line in number_lines {
-                let n_th = number_line.chars().nth(current_column).unwrap_or(' ');
-                if n_th.is_ascii_digit() {
-                    number_str.push(n_th);
-                }
-            }
-            current_problem.push(number_str.parse().unwrap());
-            current_column += 1;
-        }
-        problems.push(current_problem.clone());
-        current_problem.clear();
-        current_column += 1;
-        current_problem_column_start = current_column;
+/// One line for every problem with its columns counting from 1, operands, operator and answer,
+/// followed by anything suspicious about it, and the total at the end.
+fn report(problems: &[Problem], mode: ReadingMode) -> Vec<String> {
+    // The most common number of operands, preferring the larger count on ties
+    let mut counts = std::collections::HashMap::new();
+    for problem in problems {
+        *counts.entry(problem.numbers.len()).or_insert(0) += 1;
     }
+    let usual = counts
+        .into_iter()
+        .max_by_key(|&(operands, problems)| (problems, operands))
+        .map(|(operands, _)| operands)
+        .filter(|_| mode == ReadingMode::Rows);
+
+    let mut lines = Vec::new();
+    let mut total = Some(0isize);
+    for (i, problem) in problems.iter().enumerate() {
+        let operands: Vec<String> = problem.numbers.iter().map(|n| n.to_string()).collect();
+        let separator = format!(" {} ", problem.operator.symbol());
+        let answer = evaluate(&problem.numbers, problem.operator);
+        let mut line = format!(
+            "#{} columns {}-{}: {} = ",
+            i + 1,
+            problem.columns.start + 1,
+            problem.columns.end,
+            operands.join(&separator)
+        );
+        match answer {
+            Ok(answer) => line.push_str(&answer.to_string()),
+            Err(error) => line.push_str(&format!("ERROR {}", error)),
+        }
+
+        let mut suspicions = Vec::new();
+        if let Some(usual) = usual.filter(|&usual| usual != problem.numbers.len()) {
+            suspicions.push(Suspicion::OperandCount { usual });
+        }
+        let absorbing = matches!(problem.operator, Operator::Multiplication | Operator::Power);
+        if absorbing && problem.numbers.iter().skip(1).any(|&n| n == 0) {
+            suspicions.push(Suspicion::ZeroOperand);
+        }
+        if answer.is_ok_and(|answer| answer.unsigned_abs() > MAX_PRECISE) {
+            suspicions.push(Suspicion::Imprecise);
+        }
+        if !suspicions.is_empty() {
+            let suspicions: Vec<String> = suspicions.iter().map(|s| s.to_string()).collect();
+            line.push_str(&format!(" [suspicious: {}]", suspicions.join(", ")));
+        }
+        lines.push(line);
 
-    assert_eq!(operators.len(), problems.len());
-    problems.into_iter().zip(operators).collect()
+        total = total
+            .zip(answer.ok())
+            .and_then(|(total, answer)| total.checked_add(answer));
+    }
+    match total {
+        Some(total) => lines.push(format!("Total: {}", total)),
+        None => lines.push("Total: not available".to_string()),
+    }
+    lines
 }
 
 #[cfg(test)]
@@ -163,12 +568,237 @@ mod tests {
 
     #[test]
     fn test_part_1_simple() {
-        assert_eq!(apply_and_sum(parse_input_part_1(TEST_INPUT)), 4277556);
+        assert_eq!(apply_and_sum(parse_input_part_1(TEST_INPUT)), Ok(4277556));
     }
 
     #[test]
     fn test_part_1_final() {
-        assert_eq!(apply_and_sum(parse_input_part_1(INPUT)), 4722948564882);
+        assert_eq!(apply_and_sum(parse_input_part_1(INPUT)), Ok(4722948564882));
+    }
+
+    #[test]
+    fn test_operators() {
+        let apply = |symbol, a, b| Operator::from_symbol(symbol).unwrap().apply(a, b);
+        assert_eq!(apply('-', 3, 5), Ok(-2));
+        assert_eq!(apply('/', 7, 2), Ok(3));
+        assert_eq!(apply('/', -7, 2), Ok(-3));
+        assert_eq!(apply('/', 7, 0), Err(EvalError::DivisionByZero));
+        assert_eq!(
+            apply('/', isize::MIN, -1),
+            Err(EvalError::Overflow(Operator::Division))
+        );
+        assert_eq!(apply('<', -3, 2), Ok(-3));
+        assert_eq!(apply('>', -3, 2), Ok(2));
+        assert_eq!(apply('|', 12, 345), Ok(12345));
+        assert_eq!(apply('|', 12, 0), Ok(120));
+        assert_eq!(apply('|', -12, 3), Ok(-123));
+        assert_eq!(
+            apply('|', 12, -3),
+            Err(EvalError::NegativeOperand(Operator::Concatenation))
+        );
+        assert_eq!(
+            apply('|', isize::MAX, 1),
+            Err(EvalError::Overflow(Operator::Concatenation))
+        );
+        assert_eq!(apply('^', -2, 3), Ok(-8));
+        assert_eq!(apply('^', 5, 0), Ok(1));
+        assert_eq!(
+            apply('^', 2, -1),
+            Err(EvalError::NegativeOperand(Operator::Power))
+        );
+        assert_eq!(apply('^', 2, 64), Err(EvalError::Overflow(Operator::Power)));
+        let huge = isize::MAX - 1;
+        assert_eq!(apply('^', 0, huge), Ok(0));
+        assert_eq!(apply('^', 1, huge), Ok(1));
+        assert_eq!(apply('^', -1, huge), Ok(1));
+        assert_eq!(apply('^', -1, huge + 1), Ok(-1));
+        assert_eq!(
+            apply('^', 2, huge),
+            Err(EvalError::Overflow(Operator::Power))
+        );
+        assert_eq!(
+            apply('*', isize::MAX, 2),
+            Err(EvalError::Overflow(Operator::Multiplication))
+        );
+        assert_eq!(Operator::from_symbol('x'), None);
+    }
+
+    #[test]
+    fn test_more_operators() {
+        let input = "
+20 3  1 2 9
+4  -4 2 3 8
+2  12 3 2 7
+-  /  | ^ <
+";
+        let problems = parse_input_part_1(input);
+        assert_eq!(problems[1], (vec![3, -4, 12], Operator::Division));
+        // 20 - 4 - 2 = 14, 3 / -4 / 12 = 0, 123, (2^3)^2 = 64 and 7
+        assert_eq!(apply_and_sum(problems), Ok(208));
+
+        assert_eq!(
+            apply_and_sum(vec![(vec![1, 0], Operator::Division)]),
+            Err(EvalError::DivisionByZero)
+        );
+        assert_eq!(
+            apply_and_sum(vec![(vec![], Operator::Addition)]),
+            Err(EvalError::NoOperands)
+        );
+    }
+
+    #[test]
+    fn test_reading_modes() {
+        let numbers = |mode| -> Vec<Vec<isize>> {
+            parse_worksheet(TEST_INPUT, mode)
+                .into_iter()
+                .map(|(numbers, _)| numbers)
+                .collect()
+        };
+        assert_eq!(numbers(ReadingMode::Rows)[0], vec![123, 45, 6]);
+        assert_eq!(numbers(ReadingMode::ColumnsTopDown)[0], vec![1, 24, 356]);
+        assert_eq!(numbers(ReadingMode::ColumnsBottomUp)[0], vec![1, 42, 653]);
+        assert_eq!(numbers(ReadingMode::RightToLeft)[0], vec![356, 24, 1]);
+        assert_eq!(numbers(ReadingMode::RightToLeft)[3], vec![4, 431, 623]);
+        // Addition and multiplication do not care about the order
+        assert_eq!(
+            apply_and_sum(parse_worksheet(TEST_INPUT, ReadingMode::RightToLeft)),
+            Ok(3263827)
+        );
+        assert_eq!("bottom-up".parse(), Ok(ReadingMode::ColumnsBottomUp));
+    }
+
+    #[test]
+    fn test_worksheet() {
+        let worksheet = Worksheet::new(TEST_INPUT);
+        assert_eq!((worksheet.width, worksheet.height), (15, 4));
+        assert_eq!(worksheet.row(3, 0..4), b"*   ");
+        assert_eq!(worksheet.problem_columns(), vec![0..3, 4..7, 8..11, 12..15]);
+
+        // Problems of different widths, and far too wide to look up columns line by line
+        let count = 20_000;
+        let mut lines = [String::new(), String::new(), String::new()];
+        for i in 0..count {
+            let width = i % 3 + 1;
+            lines[0].push_str(&format!("{:>width$} ", i % 10));
+            lines[1].push_str(&format!("{:<width$} ", "7".repeat(width)));
+            lines[2].push_str(&format!("{:<width$} ", "+"));
+        }
+        let input = lines.join("\n");
+        let problems = parse_worksheet(&input, ReadingMode::Rows);
+        assert_eq!(problems.len(), count);
+        assert_eq!(problems[2], (vec![2, 777], Operator::Addition));
+        assert_eq!(
+            parse_worksheet(&input, ReadingMode::ColumnsTopDown)[2],
+            (vec![7, 7, 27], Operator::Addition)
+        );
+    }
+
+    #[test]
+    fn test_render_worksheet() {
+        let problems = parse_input_part_1(TEST_INPUT);
+        assert_eq!(
+            render_worksheet(&problems, ReadingMode::Rows).unwrap(),
+            "\
+123 328  51  64
+ 45  64 387  23
+  6  98 215 314
+*   +   *   +
+"
+        );
+        assert_eq!(
+            render_worksheet(&problems, ReadingMode::ColumnsTopDown).unwrap(),
+            "\
+146 369 532 623
+25  248 181 431
+3   8    75   4
+*   +   *   +
+"
+        );
+        assert_eq!(
+            render_worksheet(&problems, ReadingMode::ColumnsBottomUp).unwrap(),
+            "\
+3   8    75   4
+25  248 181 431
+146 369 532 623
+*   +   *   +
+"
+        );
+        assert_eq!(
+            render_worksheet(&[(vec![5, -12], Operator::Subtraction)], ReadingMode::Rows),
+            Ok("  5\n-12\n-\n".to_string())
+        );
+        assert!(
+            render_worksheet(
+                &[(vec![5, -12], Operator::Subtraction)],
+                ReadingMode::RightToLeft
+            )
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn test_render_round_trip() {
+        let modes = [
+            ReadingMode::Rows,
+            ReadingMode::ColumnsTopDown,
+            ReadingMode::ColumnsBottomUp,
+            ReadingMode::RightToLeft,
+        ];
+        let problem_sets = [
+            parse_input_part_1(INPUT),
+            parse_input_part_2(INPUT),
+            vec![
+                (vec![7], Operator::Power),
+                (vec![], Operator::Addition),
+                (vec![10, 0, 3, 1000], Operator::Concatenation),
+                (vec![5, 44], Operator::Division),
+            ],
+        ];
+        for problems in &problem_sets {
+            for mode in modes {
+                let worksheet = render_worksheet(problems, mode).unwrap();
+                assert_eq!(&parse_worksheet(&worksheet, mode), problems, "{:?}", mode);
+            }
+        }
+    }
+
+    #[test]
+    fn test_report() {
+        let problems = [
+            (vec![123, 45, 6], Operator::Multiplication),
+            (vec![328, 64, 98], Operator::Addition),
+            (vec![7, 0, 5], Operator::Multiplication),
+            (vec![10, 2], Operator::Power),
+            (vec![3000000000, 4000000], Operator::Multiplication),
+            (vec![isize::MAX, 1, 0], Operator::Addition),
+        ];
+        let input = render_worksheet(&problems, ReadingMode::Rows).unwrap();
+        let problems = parse_problems(&input, ReadingMode::Rows);
+        assert_eq!(problems[1].columns, 4..7);
+        assert_eq!(
+            report(&problems, ReadingMode::Rows),
+            vec![
+                "#1 columns 1-3: 123 * 45 * 6 = 33210",
+                "#2 columns 5-7: 328 + 64 + 98 = 490",
+                "#3 columns 9-9: 7 * 0 * 5 = 0 [suspicious: zero operand]",
+                "#4 columns 11-12: 10 ^ 2 = 100 [suspicious: usually 3 operands]",
+                "#5 columns 14-23: 3000000000 * 4000000 = 12000000000000000 \
+                 [suspicious: usually 3 operands, beyond double precision]",
+                "#6 columns 25-43: 9223372036854775807 + 1 + 0 = ERROR overflow in Addition",
+                "Total: not available",
+            ]
+        );
+
+        let rows = report(
+            &parse_problems(TEST_INPUT, ReadingMode::Rows),
+            ReadingMode::Rows,
+        );
+        assert_eq!(rows.last().unwrap(), "Total: 4277556");
+
+        // The width of a problem varies, so its operands are not counted in the columns
+        let mode = ReadingMode::ColumnsTopDown;
+        let columns = report(&parse_problems(INPUT, mode), mode);
+        assert!(columns.iter().all(|line| !line.contains("operands")));
     }
 
     #[test]
@@ -186,11 +816,11 @@ mod tests {
 
     #[test]
     fn test_part_2_simple() {
-        assert_eq!(apply_and_sum(parse_input_part_2(TEST_INPUT)), 3263827);
+        assert_eq!(apply_and_sum(parse_input_part_2(TEST_INPUT)), Ok(3263827));
     }
 
     #[test]
     fn test_part_2_final() {
-        assert_eq!(apply_and_sum(parse_input_part_2(INPUT)), 9581313737063);
+        assert_eq!(apply_and_sum(parse_input_part_2(INPUT)), Ok(9581313737063));
     }
 }